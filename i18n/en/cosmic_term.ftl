@@ -26,6 +26,13 @@ make-default = Make default
 working-directory = Working directory
 hold = Hold
 remain-open = Remain open after child process exits.
+bell = Bell
+bell-none = None
+bell-visual = Flash the terminal
+bell-urgent = Request window attention
+bell-tab-indicator = Mark background tab
+global-setting = Global setting
+use-global-setting = Use global setting
 
 ## Settings
 settings = Settings
@@ -40,6 +47,10 @@ syntax-dark = Color scheme dark
 syntax-light = Color scheme light
 default-zoom-step = Zoom steps
 opacity = Background opacity
+padding-horizontal = Horizontal padding
+padding-vertical = Vertical padding
+min-contrast = Minimum text contrast
+min-contrast-off = Off
 
 ### Font
 font = Font
@@ -51,15 +62,69 @@ default-font-weight = Normal font weight
 default-dim-font-weight = Dim font weight
 default-bold-font-weight = Bold font weight
 use-bright-bold = Make bold text brighter
+line-height = Line height
+letter-spacing = Letter spacing
+font-fallbacks = Fallback fonts
+font-fallbacks-description = Comma separated fonts to use in order for characters missing from the main font.
+
+### Cursor
+cursor = Cursor
+cursor-shape = Cursor shape
+cursor-block = Block
+cursor-underline = Underline
+cursor-beam = Beam
+cursor-blink = Blinking cursor
+cursor-blink-description = Programs can still change the shape and blinking of the cursor.
+cursor-blink-interval = Blink interval
+cursor-blink-milliseconds = {$milliseconds} ms
+cursor-blink-timeout = Stop blinking after
+cursor-blink-never = Never
+
+### Scrolling
+scrolling = Scrolling
+scroll-lines = Mouse wheel speed
+scroll-lines-count = {$lines} lines per notch
+scroll-pixels = Touchpad speed
+scroll-shift-page = Scroll by pages while holding Shift
 
 ### Splits
 splits = Splits
 focus-follow-mouse = Typing focus follows mouse
 
+### Notifications
+notifications = Notifications
+notify-command-finished = Notify when commands finish
+notify-command-finished-description = Show a notification when a long running command finishes in a tab that is not focused. Requires a shell that reports commands with OSC 133.
+notify-command-seconds = Minimum command duration
+notify-seconds = {$seconds} seconds
+command-finished = Command finished after {$seconds} seconds
+command-failed = Command failed with exit status {$status} after {$seconds} seconds
+
 ### Advanced
 advanced = Advanced
 show-headerbar = Show header
 show-header-description = Reveal the header from the right-click menu.
+restore-session = Restore previous session
+restore-session-description = Reopen the splits, tabs and working directories from the last time the window was closed.
+scrollback-lines = Scrollback lines
+scrollback-lines-count = {$lines} lines
+
+## Keyboard shortcuts
+keyboard-shortcuts = Keyboard shortcuts
+no-shortcut = No shortcut
+invalid-shortcut = Use shortcuts like Ctrl+Shift+T, separated by commas.
+reset-shortcut = Reset to default
+shortcut-invalid = {$shortcut} is not a valid shortcut
+shortcut-duplicate = {$shortcut} is set more than once
+shortcut-replaces-default = {$shortcut} replaces the default binding for {$action}
+copy-or-sigint = Copy or interrupt
+paste-primary = Paste primary selection
+pane-focus-left = Focus split on the left
+pane-focus-down = Focus split below
+pane-focus-up = Focus split above
+pane-focus-right = Focus split on the right
+activate-tab = Activate tab {$number}
+activate-last-tab = Activate last tab
 
 # Find
 find-placeholder = Find...
@@ -83,6 +148,10 @@ copy = Copy
 paste = Paste
 select-all = Select all
 find = Find
+previous-prompt = Previous prompt
+next-prompt = Next prompt
+select-command-output = Select command output
+vi-mode = Vi mode
 
 ## View
 view = View
@@ -4,11 +4,13 @@
 use super::context_page::ContextPage;
 use super::message::Message;
 use crate::config::{color_scheme::ColorSchemeKind, profile::ProfileId};
+use crate::fl;
 use cosmic::widget::menu::action::MenuAction;
 use cosmic::widget::pane_grid;
 use cosmic::widget::segmented_button;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum Action {
     About,
     ColorSchemes(ColorSchemeKind),
@@ -27,7 +29,10 @@ pub enum Action {
     PastePrimary,
     ProfileOpen(ProfileId),
     Profiles,
+    PromptNext,
+    PromptPrevious,
     SelectAll,
+    SelectCommandOutput,
     Settings,
     ShowHeaderBar(bool),
     TabActivate0,
@@ -44,6 +49,7 @@ pub enum Action {
     // TabNewNoProfile,
     TabNext,
     TabPrev,
+    ToggleViMode,
     WindowClose,
     WindowNew,
     ZoomIn,
@@ -52,6 +58,94 @@ pub enum Action {
 }
 
 impl Action {
+    // Actions that can be assigned shortcuts in the key bindings editor
+    pub const BINDABLE: &'static [Self] = &[
+        Self::Copy,
+        Self::CopyOrSigint,
+        Self::Paste,
+        Self::PastePrimary,
+        Self::SelectAll,
+        Self::Find,
+        Self::PromptPrevious,
+        Self::PromptNext,
+        Self::SelectCommandOutput,
+        Self::ToggleViMode,
+        Self::TabNew,
+        Self::TabClose,
+        Self::TabNext,
+        Self::TabPrev,
+        Self::TabActivate0,
+        Self::TabActivate1,
+        Self::TabActivate2,
+        Self::TabActivate3,
+        Self::TabActivate4,
+        Self::TabActivate5,
+        Self::TabActivate6,
+        Self::TabActivate7,
+        Self::TabActivate8,
+        Self::PaneSplitHorizontal,
+        Self::PaneSplitVertical,
+        Self::PaneToggleMaximized,
+        Self::PaneFocusLeft,
+        Self::PaneFocusDown,
+        Self::PaneFocusUp,
+        Self::PaneFocusRight,
+        Self::ZoomIn,
+        Self::ZoomOut,
+        Self::ZoomReset,
+        Self::WindowNew,
+        Self::WindowClose,
+        Self::Settings,
+        Self::Profiles,
+        Self::About,
+    ];
+
+    pub fn title(&self) -> String {
+        match self {
+            Self::About => fl!("menu-about"),
+            Self::ColorSchemes(_) => fl!("menu-color-schemes"),
+            Self::Copy => fl!("copy"),
+            Self::CopyOrSigint => fl!("copy-or-sigint"),
+            Self::Find => fl!("find"),
+            Self::PaneFocusDown => fl!("pane-focus-down"),
+            Self::PaneFocusLeft => fl!("pane-focus-left"),
+            Self::PaneFocusRight => fl!("pane-focus-right"),
+            Self::PaneFocusUp => fl!("pane-focus-up"),
+            Self::PaneSplitHorizontal => fl!("split-horizontal"),
+            Self::PaneSplitVertical => fl!("split-vertical"),
+            Self::PaneToggleMaximized => fl!("pane-toggle-maximize"),
+            Self::Paste => fl!("paste"),
+            Self::PastePrimary => fl!("paste-primary"),
+            Self::ProfileOpen(_) => fl!("profile"),
+            Self::Profiles => fl!("menu-profiles"),
+            Self::PromptNext => fl!("next-prompt"),
+            Self::PromptPrevious => fl!("previous-prompt"),
+            Self::SelectAll => fl!("select-all"),
+            Self::SelectCommandOutput => fl!("select-command-output"),
+            Self::Settings => fl!("menu-settings"),
+            Self::ShowHeaderBar(_) => fl!("show-headerbar"),
+            Self::TabActivate0 => fl!("activate-tab", number = 1),
+            Self::TabActivate1 => fl!("activate-tab", number = 2),
+            Self::TabActivate2 => fl!("activate-tab", number = 3),
+            Self::TabActivate3 => fl!("activate-tab", number = 4),
+            Self::TabActivate4 => fl!("activate-tab", number = 5),
+            Self::TabActivate5 => fl!("activate-tab", number = 6),
+            Self::TabActivate6 => fl!("activate-tab", number = 7),
+            Self::TabActivate7 => fl!("activate-tab", number = 8),
+            Self::TabActivate8 => fl!("activate-last-tab"),
+            Self::TabClose => fl!("close-tab"),
+            Self::TabNew => fl!("new-tab"),
+            Self::TabNext => fl!("next-tab"),
+            Self::TabPrev => fl!("previous-tab"),
+            Self::ToggleViMode => fl!("vi-mode"),
+            Self::WindowClose => fl!("quit"),
+            Self::WindowNew => fl!("new-window"),
+            Self::ZoomIn => fl!("zoom-in"),
+            Self::ZoomOut => fl!("zoom-out"),
+            Self::ZoomReset => fl!("zoom-reset"),
+        }
+    }
+
     pub(super) fn message(&self, entity_opt: Option<segmented_button::Entity>) -> Message {
         match self {
             Self::About => Message::ToggleContextPage(ContextPage::About),
@@ -73,7 +167,10 @@ impl Action {
             Self::PastePrimary => Message::PastePrimary(entity_opt),
             Self::ProfileOpen(profile_id) => Message::ProfileOpen(*profile_id),
             Self::Profiles => Message::ToggleContextPage(ContextPage::Profiles),
+            Self::PromptNext => Message::PromptNext(entity_opt),
+            Self::PromptPrevious => Message::PromptPrevious(entity_opt),
             Self::SelectAll => Message::SelectAll(entity_opt),
+            Self::SelectCommandOutput => Message::SelectCommandOutput(entity_opt),
             Self::Settings => Message::ToggleContextPage(ContextPage::Settings),
             Self::ShowHeaderBar(show_headerbar) => Message::ShowHeaderBar(*show_headerbar),
             Self::TabActivate0 => Message::TabActivateJump(0),
@@ -90,6 +187,7 @@ impl Action {
             // Self::TabNewNoProfile => Message::TabNewNoProfile,
             Self::TabNext => Message::TabNext,
             Self::TabPrev => Message::TabPrev,
+            Self::ToggleViMode => Message::ToggleViMode(entity_opt),
             Self::WindowClose => Message::WindowClose,
             Self::WindowNew => Message::WindowNew,
             Self::ZoomIn => Message::ZoomIn,
@@ -4,11 +4,14 @@ use super::flags::Flags;
 use super::message::Message;
 use crate::config::app_theme::AppTheme;
 use crate::config::color_scheme::{ColorScheme, ColorSchemeId, ColorSchemeKind};
-use crate::config::config::Config;
+use crate::config::config::{Config, CursorShape};
 use crate::config::constants::{CONFIG_VERSION, COSMIC_THEME_LIGHT};
-use crate::config::profile::{Profile, ProfileId};
+use crate::config::profile::{BellMode, Profile, ProfileId};
+use crate::config::session::{Session, SessionPane, SessionState, SessionTab};
 use crate::dnd::DndDrop;
-use crate::key_bind::key_binds;
+use crate::key_bind::{
+    default_key_binds, key_bind_conflicts, key_binds, parse_key_bind, shortcuts_for_action,
+};
 use crate::localization::LANGUAGE_SORTER;
 use crate::menu::menu_bar;
 use crate::terminal::terminal::{Terminal, TerminalPaneGrid};
@@ -46,6 +49,7 @@ use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
     env, fs, process,
     sync::{atomic::Ordering, Mutex},
+    time::Duration,
 };
 
 use tokio::sync::mpsc;
@@ -56,8 +60,16 @@ pub struct App {
     pane_model: TerminalPaneGrid,
     config_handler: Option<cosmic_config::Config>,
     config: Config,
+    state_handler: Option<cosmic_config::Config>,
+    state: SessionState,
     key_binds: HashMap<KeyBind, Action>,
     app_themes: Vec<String>,
+    bell_mode_names: Vec<String>,
+    cursor_shape_names: Vec<String>,
+    cursor_blink_interval_names: Vec<String>,
+    cursor_blink_intervals: Vec<u32>,
+    cursor_blink_timeout_names: Vec<String>,
+    cursor_blink_timeouts: Vec<u32>,
     font_names: Vec<String>,
     font_size_names: Vec<String>,
     font_sizes: Vec<u16>,
@@ -70,6 +82,15 @@ pub struct App {
     curr_font_stretches: Vec<Stretch>,
     zoom_step_names: Vec<String>,
     zoom_steps: Vec<u16>,
+    notify_seconds_names: Vec<String>,
+    notify_seconds: Vec<u32>,
+    scrollback_lines_names: Vec<String>,
+    scrollback_lines: Vec<u32>,
+    // Choices for profile overrides, with the global setting first
+    profile_cursor_shape_names: Vec<String>,
+    profile_font_names: Vec<String>,
+    profile_font_size_names: Vec<String>,
+    profile_scrollback_lines_names: Vec<String>,
     theme_names_dark: Vec<String>,
     theme_names_light: Vec<String>,
     themes: HashMap<(String, ColorSchemeKind), TermColors>,
@@ -79,6 +100,8 @@ pub struct App {
     find: bool,
     find_search_id: widget::Id,
     find_search_value: String,
+    // Direction of the search when the find bar was opened from vi mode
+    find_vi_forwards_opt: Option<bool>,
     term_event_tx_opt: Option<mpsc::Sender<(pane_grid::Pane, segmented_button::Entity, TermEvent)>>,
     startup_options: Option<tty::Options>,
     term_config: term::Config,
@@ -88,8 +111,35 @@ pub struct App {
     color_scheme_rename_id: widget::Id,
     color_scheme_tab_model: widget::segmented_button::SingleSelectModel,
     profile_expanded: Option<ProfileId>,
+    key_bind_editing: Option<(Action, String)>,
+    key_bind_edit_id: widget::Id,
     show_advanced_font_settings: bool,
+    // Text of the font fallback input, which is a comma separated list
+    font_fallbacks_value: String,
     modifiers: Modifiers,
+    window_focused: bool,
+}
+
+// Control for a profile override, with a button to go back to the global setting when the
+// profile overrides it
+fn profile_override<'a>(
+    control: impl Into<Element<'a, Message>>,
+    reset_opt: Option<Message>,
+    spacing: u16,
+) -> Element<'a, Message> {
+    let mut row = widget::row::with_capacity(2)
+        .align_items(Alignment::Center)
+        .spacing(spacing);
+    if let Some(reset) = reset_opt {
+        row = row.push(widget::tooltip(
+            widget::button(icon_cache_get("edit-undo-symbolic", 16))
+                .on_press(reset)
+                .style(style::Button::Icon),
+            fl!("use-global-setting"),
+            widget::tooltip::Position::Top,
+        ));
+    }
+    row.push(control).into()
 }
 
 impl App {
@@ -164,6 +214,9 @@ impl App {
         // Update color schemes
         self.update_color_schemes();
 
+        // Update key bindings
+        self.key_binds = key_binds(&self.config);
+
         // Update terminal window background color
         {
             let color = Color::from(theme.cosmic().background.base);
@@ -249,6 +302,213 @@ impl App {
         Command::none()
     }
 
+    fn save_key_binds(&mut self, value: BTreeMap<String, Option<Action>>) -> Command<Message> {
+        match &self.config_handler {
+            Some(config_handler) => {
+                if let Err(err) = self.config.set_key_binds(config_handler, value) {
+                    log::error!("failed to save config: {}", err);
+                }
+            }
+            None => {
+                self.config.key_binds = value;
+                log::warn!("failed to save config \"key_binds\": no config handler");
+            }
+        }
+        self.key_binds = key_binds(&self.config);
+        Command::none()
+    }
+
+    // Store the pane layout and tabs so they can be reopened on the next start
+    fn save_session(&mut self) {
+        fn session_pane(
+            panes: &pane_grid::State<segmented_button::SingleSelectModel>,
+            node: &pane_grid::Node,
+        ) -> SessionPane {
+            match node {
+                pane_grid::Node::Split {
+                    axis, ratio, a, b, ..
+                } => SessionPane::Split {
+                    axis: (*axis).into(),
+                    ratio: *ratio,
+                    a: Box::new(session_pane(panes, a)),
+                    b: Box::new(session_pane(panes, b)),
+                },
+                pane_grid::Node::Pane(pane) => {
+                    let mut tabs = Vec::new();
+                    let mut active = 0;
+                    if let Some(tab_model) = panes.get(*pane) {
+                        for entity in tab_model.iter() {
+                            if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
+                                let terminal = terminal.lock().unwrap();
+                                if entity == tab_model.active() {
+                                    active = tabs.len();
+                                }
+                                tabs.push(SessionTab {
+                                    profile_id: terminal.profile_id_opt,
+                                    tab_title_override: terminal.tab_title_override.clone(),
+                                    working_directory: terminal.current_dir(),
+                                });
+                            }
+                        }
+                    }
+                    SessionPane::Tabs { tabs, active }
+                }
+            }
+        }
+
+        let session = Session {
+            root: session_pane(&self.pane_model.panes, self.pane_model.panes.layout()),
+        };
+        match &self.state_handler {
+            Some(state_handler) => {
+                if let Err(err) = self.state.set_session(state_handler, Some(session)) {
+                    log::warn!("failed to save session: {}", err);
+                }
+            }
+            None => {
+                self.state.session = Some(session);
+                log::warn!("failed to save session: no state handler");
+            }
+        }
+    }
+
+    // Forget the saved session once the last tab is gone, so it is not restored again
+    fn clear_session(&mut self) {
+        match &self.state_handler {
+            Some(state_handler) => {
+                if let Err(err) = self.state.set_session(state_handler, None) {
+                    log::warn!("failed to clear session: {}", err);
+                }
+            }
+            None => {
+                self.state.session = None;
+                log::warn!("failed to clear session: no state handler");
+            }
+        }
+    }
+
+    fn restore_session(&mut self, session: Session) -> Command<Message> {
+        fn configuration(
+            session_pane: &SessionPane,
+        ) -> pane_grid::Configuration<segmented_button::SingleSelectModel> {
+            match session_pane {
+                SessionPane::Split { axis, ratio, a, b } => pane_grid::Configuration::Split {
+                    axis: (*axis).into(),
+                    ratio: *ratio,
+                    a: Box::new(configuration(a)),
+                    b: Box::new(configuration(b)),
+                },
+                SessionPane::Tabs { .. } => pane_grid::Configuration::Pane(
+                    segmented_button::ModelBuilder::default().build(),
+                ),
+            }
+        }
+
+        // The new layout mirrors the session, so walk both to match panes with their tabs
+        fn pane_tabs<'a>(
+            node: &pane_grid::Node,
+            session_pane: &'a SessionPane,
+            pane_tabs_list: &mut Vec<(pane_grid::Pane, &'a [SessionTab], usize)>,
+        ) {
+            match (node, session_pane) {
+                (
+                    pane_grid::Node::Split { a, b, .. },
+                    SessionPane::Split {
+                        a: session_a,
+                        b: session_b,
+                        ..
+                    },
+                ) => {
+                    pane_tabs(a, session_a, pane_tabs_list);
+                    pane_tabs(b, session_b, pane_tabs_list);
+                }
+                (pane_grid::Node::Pane(pane), SessionPane::Tabs { tabs, active }) => {
+                    pane_tabs_list.push((*pane, tabs, *active));
+                }
+                _ => {
+                    log::warn!("restored pane layout does not match session");
+                }
+            }
+        }
+
+        self.pane_model.panes = pane_grid::State::with_configuration(configuration(&session.root));
+        let mut pane_tabs_list = Vec::new();
+        pane_tabs(
+            self.pane_model.panes.layout(),
+            &session.root,
+            &mut pane_tabs_list,
+        );
+        self.pane_model.panes_created = pane_tabs_list.len();
+        self.terminal_ids.clear();
+
+        let mut commands = Vec::new();
+        for (pane, tabs, active) in pane_tabs_list.iter() {
+            self.terminal_ids.insert(*pane, widget::Id::unique());
+            for tab in tabs.iter() {
+                // Profiles may have been removed since the session was saved
+                let profile_id_opt = tab
+                    .profile_id
+                    .filter(|profile_id| self.config.profiles.contains_key(profile_id));
+                commands.push(self.create_and_focus_terminal(*pane, profile_id_opt, Some(tab)));
+            }
+            if tabs.is_empty() {
+                commands
+                    .push(self.create_and_focus_new_terminal(*pane, self.get_default_profile()));
+            }
+            if let Some(tab_model) = self.pane_model.panes.get_mut(*pane) {
+                tab_model.activate_position(*active as u16);
+            }
+        }
+
+        if let Some((pane, _, _)) = pane_tabs_list.first() {
+            self.pane_model.focus = *pane;
+        }
+        commands.push(self.update_title(None));
+        commands.push(self.update_focus());
+        Command::batch(commands)
+    }
+
+    // Blink interval of the focused terminal, if its cursor is blinking
+    fn cursor_blink_interval(&self) -> Option<Duration> {
+        if !self.window_focused {
+            return None;
+        }
+        let tab_model = self.pane_model.active()?;
+        let terminal = tab_model
+            .data::<Mutex<Terminal>>(tab_model.active())?
+            .lock()
+            .unwrap();
+        terminal
+            .cursor_blinking()
+            .then(|| terminal.cursor_blink_interval())
+    }
+
+    /// Show a desktop notification, errors are only logged
+    fn notify(&self, summary: String, body: String) -> Command<Message> {
+        #[cfg(unix)]
+        {
+            let notification = crate::notify::DesktopNotification {
+                app_name: fl!("cosmic-terminal"),
+                app_id: Self::APP_ID.to_string(),
+                summary,
+                body,
+            };
+            Command::perform(
+                async move {
+                    if let Err(err) = notification.send().await {
+                        log::warn!("failed to send notification: {}", err);
+                    }
+                },
+                |()| message::none(),
+            )
+        }
+        #[cfg(not(unix))]
+        {
+            log::info!("notifications are not supported: {}: {}", summary, body);
+            Command::none()
+        }
+    }
+
     fn update_focus(&self) -> Command<Message> {
         if self.find {
             widget::text_input::focus(self.find_search_id.clone())
@@ -262,6 +522,10 @@ impl App {
     // Call this any time the tab changes
     fn update_title(&mut self, pane: Option<pane_grid::Pane>) -> Command<Message> {
         let pane = pane.unwrap_or(self.pane_model.focus);
+        // The bell indicator is only shown until the tab is seen
+        if let Some(tab_model) = self.pane_model.panes.get_mut(pane) {
+            tab_model.icon_remove(tab_model.active());
+        }
         if let Some(tab_model) = self.pane_model.panes.get(pane) {
             let (header_title, window_title) = match tab_model.text(tab_model.active()) {
                 Some(tab_title) => (
@@ -504,6 +768,95 @@ impl App {
         widget::settings::view_column(sections).into()
     }
 
+    fn key_bindings(&self) -> Element<Message> {
+        let cosmic_theme::Spacing {
+            space_xxs,
+            space_xxxs,
+            ..
+        } = self.core().system_theme().cosmic().spacing;
+
+        let conflicts = key_bind_conflicts(&self.config);
+        let mut sections = Vec::with_capacity(1 + conflicts.len());
+
+        for conflict in conflicts {
+            sections.push(
+                widget::row::with_children(vec![
+                    icon_cache_get("dialog-warning-symbolic", 16)
+                        .style(style::Svg::custom(|theme| {
+                            let cosmic = theme.cosmic();
+                            widget::svg::Appearance {
+                                color: Some(cosmic.warning_text_color().into()),
+                            }
+                        }))
+                        .into(),
+                    widget::text(conflict)
+                        .style(style::Text::Custom(|theme| {
+                            let cosmic = theme.cosmic();
+                            //TODO: re-export in libcosmic
+                            iced::widget::text::Appearance {
+                                color: Some(cosmic.warning_text_color().into()),
+                            }
+                        }))
+                        .into(),
+                ])
+                .spacing(space_xxxs)
+                .into(),
+            );
+        }
+
+        let mut section = widget::settings::view_section("");
+        for &action in Action::BINDABLE {
+            let shortcuts = shortcuts_for_action(&self.key_binds, action).join(", ");
+            let control: Element<_> = match &self.key_bind_editing {
+                Some((editing_action, value)) if *editing_action == action => {
+                    let valid = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|shortcut| !shortcut.is_empty())
+                        .all(|shortcut| parse_key_bind(shortcut).is_some());
+                    let mut column = widget::column::with_capacity(2).push(
+                        widget::text_input(fl!("no-shortcut"), value)
+                            .id(self.key_bind_edit_id.clone())
+                            .on_input(move |value| Message::KeyBindEdit(action, value))
+                            .on_submit(Message::KeyBindSubmit)
+                            .width(Length::Fixed(200.0)),
+                    );
+                    if !valid {
+                        column = column.push(widget::text::caption(fl!("invalid-shortcut")));
+                    }
+                    column.spacing(space_xxxs).into()
+                }
+                _ => widget::button::standard(if shortcuts.is_empty() {
+                    fl!("no-shortcut")
+                } else {
+                    shortcuts.clone()
+                })
+                .on_press(Message::KeyBindEdit(action, shortcuts))
+                .into(),
+            };
+            section = section.add(
+                widget::settings::item::builder(action.title()).control(
+                    widget::row::with_children(vec![
+                        control,
+                        widget::tooltip(
+                            widget::button(icon_cache_get("edit-undo-symbolic", 16))
+                                .on_press(Message::KeyBindReset(action))
+                                .style(style::Button::Icon),
+                            fl!("reset-shortcut"),
+                            widget::tooltip::Position::Top,
+                        )
+                        .into(),
+                    ])
+                    .align_items(Alignment::Center)
+                    .spacing(space_xxs),
+                ),
+            );
+        }
+        sections.push(section.into());
+
+        widget::settings::view_column(sections).into()
+    }
+
     fn profiles(&self) -> Element<Message> {
         let cosmic_theme::Spacing {
             space_s,
@@ -523,6 +876,9 @@ impl App {
                 };
 
                 let expanded = self.profile_expanded == Some(profile_id);
+                let opacity = self.config.profile_opacity(Some(profile_id));
+                let (padding_horizontal, padding_vertical) =
+                    self.config.profile_padding(Some(profile_id));
 
                 profiles_section = profiles_section.add(
                     widget::settings::item::builder(profile_name).control(
@@ -661,6 +1017,108 @@ impl App {
                             ])
                             .align_items(Alignment::Center)
                             .padding([0, space_s]),
+                        )
+                        .add(
+                            widget::settings::item::builder(fl!("bell")).control(widget::dropdown(
+                                &self.bell_mode_names,
+                                BellMode::ALL
+                                    .iter()
+                                    .position(|bell_mode| *bell_mode == profile.bell),
+                                move |index| Message::ProfileBell(profile_id, index),
+                            )),
+                        )
+                        .add(widget::settings::item::builder(fl!("font")).control(
+                            widget::dropdown(
+                                &self.profile_font_names,
+                                Some(profile.font_name.as_ref().map_or(0, |font_name| {
+                                    self.font_names
+                                        .iter()
+                                        .position(|name| name == font_name)
+                                        .map_or(0, |index| index + 1)
+                                })),
+                                move |index| Message::ProfileFont(profile_id, index),
+                            ),
+                        ))
+                        .add(
+                            widget::settings::item::builder(fl!("default-font-size")).control(
+                                widget::dropdown(
+                                    &self.profile_font_size_names,
+                                    Some(profile.font_size.map_or(0, |font_size| {
+                                        self.font_sizes
+                                            .iter()
+                                            .position(|size| *size == font_size)
+                                            .map_or(0, |index| index + 1)
+                                    })),
+                                    move |index| Message::ProfileFontSize(profile_id, index),
+                                ),
+                            ),
+                        )
+                        .add(
+                            widget::settings::item::builder(fl!("opacity"))
+                                .description(format!("{}%", opacity))
+                                .control(profile_override(
+                                    widget::slider(0..=100, opacity, move |opacity| {
+                                        Message::ProfileOpacity(profile_id, Some(opacity))
+                                    }),
+                                    profile
+                                        .opacity
+                                        .map(|_| Message::ProfileOpacity(profile_id, None)),
+                                    space_xxs,
+                                )),
+                        )
+                        .add(
+                            widget::settings::item::builder(fl!("padding-horizontal"))
+                                .description(format!("{}px", padding_horizontal))
+                                .control(profile_override(
+                                    widget::slider(0..=32, padding_horizontal, move |padding| {
+                                        Message::ProfilePaddingHorizontal(profile_id, Some(padding))
+                                    }),
+                                    profile.padding_horizontal.map(|_| {
+                                        Message::ProfilePaddingHorizontal(profile_id, None)
+                                    }),
+                                    space_xxs,
+                                )),
+                        )
+                        .add(
+                            widget::settings::item::builder(fl!("padding-vertical"))
+                                .description(format!("{}px", padding_vertical))
+                                .control(profile_override(
+                                    widget::slider(0..=32, padding_vertical, move |padding| {
+                                        Message::ProfilePaddingVertical(profile_id, Some(padding))
+                                    }),
+                                    profile
+                                        .padding_vertical
+                                        .map(|_| Message::ProfilePaddingVertical(profile_id, None)),
+                                    space_xxs,
+                                )),
+                        )
+                        .add(
+                            widget::settings::item::builder(fl!("cursor-shape")).control(
+                                widget::dropdown(
+                                    &self.profile_cursor_shape_names,
+                                    Some(profile.cursor_shape.map_or(0, |cursor_shape| {
+                                        CursorShape::ALL
+                                            .iter()
+                                            .position(|shape| *shape == cursor_shape)
+                                            .map_or(0, |index| index + 1)
+                                    })),
+                                    move |index| Message::ProfileCursorShape(profile_id, index),
+                                ),
+                            ),
+                        )
+                        .add(
+                            widget::settings::item::builder(fl!("scrollback-lines")).control(
+                                widget::dropdown(
+                                    &self.profile_scrollback_lines_names,
+                                    Some(profile.scrollback_lines.map_or(0, |lines| {
+                                        self.scrollback_lines
+                                            .iter()
+                                            .position(|scrollback| *scrollback == lines)
+                                            .map_or(0, |index| index + 1)
+                                    })),
+                                    move |index| Message::ProfileScrollbackLines(profile_id, index),
+                                ),
+                            ),
                         );
 
                     let padding = Padding {
@@ -776,6 +1234,40 @@ impl App {
                     .control(widget::slider(0..=100, self.config.opacity, |opacity| {
                         Message::Opacity(opacity)
                     })),
+            )
+            .add(
+                widget::settings::item::builder(fl!("padding-horizontal"))
+                    .description(format!("{}px", self.config.padding_horizontal))
+                    .control(widget::slider(
+                        0..=32,
+                        self.config.padding_horizontal,
+                        Message::PaddingHorizontal,
+                    )),
+            )
+            .add(
+                widget::settings::item::builder(fl!("padding-vertical"))
+                    .description(format!("{}px", self.config.padding_vertical))
+                    .control(widget::slider(
+                        0..=32,
+                        self.config.padding_vertical,
+                        Message::PaddingVertical,
+                    )),
+            )
+            .add(
+                widget::settings::item::builder(fl!("min-contrast"))
+                    .description(if self.config.min_contrast_mul_100 > 100 {
+                        format!("{}:1", self.config.min_contrast())
+                    } else {
+                        fl!("min-contrast-off")
+                    })
+                    .control(
+                        widget::slider(
+                            100..=700,
+                            self.config.min_contrast_mul_100,
+                            Message::MinContrast,
+                        )
+                        .step(10u16),
+                    ),
             );
 
         let mut font_section = widget::settings::view_section(fl!("font"))
@@ -847,6 +1339,41 @@ impl App {
                 .add(
                     widget::settings::item::builder(fl!("use-bright-bold"))
                         .toggler(self.config.use_bright_bold, Message::UseBrightBold),
+                )
+                .add(
+                    widget::settings::item::builder(fl!("line-height"))
+                        .description(format!(
+                            "{}×",
+                            f32::from(self.config.line_height_mul_100) / 100.0
+                        ))
+                        .control(
+                            widget::slider(
+                                100..=250,
+                                self.config.line_height_mul_100,
+                                Message::LineHeight,
+                            )
+                            .step(5u16),
+                        ),
+                )
+                .add(
+                    widget::settings::item::builder(fl!("letter-spacing"))
+                        .description(format!("{}px", self.config.letter_spacing))
+                        .control(widget::slider(
+                            -2..=16,
+                            self.config.letter_spacing,
+                            Message::LetterSpacing,
+                        )),
+                )
+                .add(
+                    widget::settings::item::builder(fl!("font-fallbacks"))
+                        .description(fl!("font-fallbacks-description"))
+                        .control(
+                            widget::text_input(
+                                "Symbols Nerd Font, Noto Sans CJK SC, Noto Color Emoji",
+                                &self.font_fallbacks_value,
+                            )
+                            .on_input(Message::FontFallbacks),
+                        ),
                 );
             let padding = Padding {
                 top: 0.0,
@@ -861,21 +1388,148 @@ impl App {
             font_section = font_section.add(advanced_font_settings());
         }
 
+        let cursor_shape_selected = CursorShape::ALL
+            .iter()
+            .position(|shape| shape == &self.config.cursor_shape);
+        let mut cursor_section = widget::settings::view_section(fl!("cursor"))
+            .add(
+                widget::settings::item::builder(fl!("cursor-shape")).control(widget::dropdown(
+                    &self.cursor_shape_names,
+                    cursor_shape_selected,
+                    Message::CursorShape,
+                )),
+            )
+            .add(
+                widget::settings::item::builder(fl!("cursor-blink"))
+                    .description(fl!("cursor-blink-description"))
+                    .toggler(self.config.cursor_blink, Message::CursorBlink),
+            );
+        if self.config.cursor_blink {
+            let interval_selected = self
+                .cursor_blink_intervals
+                .iter()
+                .position(|interval| interval == &self.config.cursor_blink_interval_ms);
+            let timeout_selected = self
+                .cursor_blink_timeouts
+                .iter()
+                .position(|timeout| timeout == &self.config.cursor_blink_timeout_seconds);
+            cursor_section = cursor_section
+                .add(
+                    widget::settings::item::builder(fl!("cursor-blink-interval")).control(
+                        widget::dropdown(
+                            &self.cursor_blink_interval_names,
+                            interval_selected,
+                            Message::CursorBlinkInterval,
+                        ),
+                    ),
+                )
+                .add(
+                    widget::settings::item::builder(fl!("cursor-blink-timeout")).control(
+                        widget::dropdown(
+                            &self.cursor_blink_timeout_names,
+                            timeout_selected,
+                            Message::CursorBlinkTimeout,
+                        ),
+                    ),
+                );
+        }
+
+        let scrolling_section = widget::settings::view_section(fl!("scrolling"))
+            .add(
+                widget::settings::item::builder(fl!("scroll-lines"))
+                    .description(fl!("scroll-lines-count", lines = self.config.scroll_lines))
+                    .control(widget::slider(
+                        1..=10,
+                        self.config.scroll_lines,
+                        Message::ScrollLines,
+                    )),
+            )
+            .add(
+                widget::settings::item::builder(fl!("scroll-pixels"))
+                    .description(format!(
+                        "{}×",
+                        f32::from(self.config.scroll_pixels_mul_100) / 100.0
+                    ))
+                    .control(
+                        widget::slider(
+                            50..=1000,
+                            self.config.scroll_pixels_mul_100,
+                            Message::ScrollPixels,
+                        )
+                        .step(50u16),
+                    ),
+            )
+            .add(
+                widget::settings::item::builder(fl!("scroll-shift-page"))
+                    .toggler(self.config.scroll_shift_page, Message::ScrollShiftPage),
+            );
+
         let splits_section = widget::settings::view_section(fl!("splits")).add(
             widget::settings::item::builder(fl!("focus-follow-mouse"))
                 .toggler(self.config.focus_follow_mouse, Message::FocusFollowMouse),
         );
 
-        let advanced_section = widget::settings::view_section(fl!("advanced")).add(
-            widget::settings::item::builder(fl!("show-headerbar"))
-                .description(fl!("show-header-description"))
-                .toggler(self.config.show_headerbar, Message::ShowHeaderBar),
+        let mut notifications_section = widget::settings::view_section(fl!("notifications")).add(
+            widget::settings::item::builder(fl!("notify-command-finished"))
+                .description(fl!("notify-command-finished-description"))
+                .toggler(
+                    self.config.notify_command_finished,
+                    Message::NotifyCommandFinished,
+                ),
         );
+        if self.config.notify_command_finished {
+            let notify_seconds_selected = self
+                .notify_seconds
+                .iter()
+                .position(|seconds| seconds == &self.config.notify_command_seconds);
+            notifications_section = notifications_section.add(
+                widget::settings::item::builder(fl!("notify-command-seconds")).control(
+                    widget::dropdown(
+                        &self.notify_seconds_names,
+                        notify_seconds_selected,
+                        Message::NotifyCommandSeconds,
+                    ),
+                ),
+            );
+        }
+
+        let scrollback_lines_selected = self
+            .scrollback_lines
+            .iter()
+            .position(|lines| lines == &self.config.scrollback_lines);
+        let advanced_section = widget::settings::view_section(fl!("advanced"))
+            .add(
+                widget::settings::item::builder(fl!("scrollback-lines")).control(widget::dropdown(
+                    &self.scrollback_lines_names,
+                    scrollback_lines_selected,
+                    Message::ScrollbackLines,
+                )),
+            )
+            .add(
+                widget::settings::item::builder(fl!("restore-session"))
+                    .description(fl!("restore-session-description"))
+                    .toggler(self.config.restore_session, Message::RestoreSession),
+            )
+            .add(
+                widget::settings::item::builder(fl!("show-headerbar"))
+                    .description(fl!("show-header-description"))
+                    .toggler(self.config.show_headerbar, Message::ShowHeaderBar),
+            )
+            .add(
+                widget::settings::item::builder(fl!("keyboard-shortcuts")).control(
+                    widget::button(icon_cache_get("go-next-symbolic", 16))
+                        .on_press(Message::ToggleContextPage(ContextPage::KeyBinds))
+                        .style(style::Button::Icon),
+                ),
+            );
 
         widget::settings::view_column(vec![
             appearance_section.into(),
             font_section.into(),
+            cursor_section.into(),
+            scrolling_section.into(),
             splits_section.into(),
+            notifications_section.into(),
             advanced_section.into(),
         ])
         .into()
@@ -888,6 +1542,15 @@ impl App {
         &mut self,
         pane: pane_grid::Pane,
         profile_id_opt: Option<ProfileId>,
+    ) -> Command<Message> {
+        self.create_and_focus_terminal(pane, profile_id_opt, None)
+    }
+
+    fn create_and_focus_terminal(
+        &mut self,
+        pane: pane_grid::Pane,
+        profile_id_opt: Option<ProfileId>,
+        session_tab_opt: Option<&SessionTab>,
     ) -> Command<Message> {
         self.pane_model.focus = pane;
         match &self.term_event_tx_opt {
@@ -909,7 +1572,7 @@ impl App {
                         let current_pane = self.pane_model.focus;
                         if let Some(tab_model) = self.pane_model.active_mut() {
                             // Use the profile options, startup options, or defaults
-                            let (options, tab_title_override) = match profile_id_opt
+                            let (mut options, mut tab_title_override) = match profile_id_opt
                                 .and_then(|profile_id| self.config.profiles.get(&profile_id))
                             {
                                 Some(profile) => {
@@ -938,6 +1601,13 @@ impl App {
                                 }
                                 None => (self.startup_options.take().unwrap_or_default(), None),
                             };
+                            // Restored tabs keep their working directory and title
+                            if let Some(session_tab) = session_tab_opt {
+                                if let Some(working_directory) = &session_tab.working_directory {
+                                    options.working_directory = Some(working_directory.clone());
+                                }
+                                tab_title_override = session_tab.tab_title_override.clone();
+                            }
                             let entity = tab_model
                                 .insert()
                                 .text(
@@ -1050,6 +1720,30 @@ impl Application for App {
         }
 
         let app_themes = vec![fl!("match-desktop"), fl!("dark"), fl!("light")];
+        let bell_mode_names = BellMode::ALL.iter().map(BellMode::title).collect();
+        let cursor_shape_names: Vec<String> =
+            CursorShape::ALL.iter().map(CursorShape::title).collect();
+
+        let mut cursor_blink_interval_names = Vec::new();
+        let mut cursor_blink_intervals = Vec::new();
+        for milliseconds in [250, 500, 750, 1000, 1500] {
+            cursor_blink_interval_names.push(fl!(
+                "cursor-blink-milliseconds",
+                milliseconds = milliseconds
+            ));
+            cursor_blink_intervals.push(milliseconds);
+        }
+
+        let mut cursor_blink_timeout_names = Vec::new();
+        let mut cursor_blink_timeouts = Vec::new();
+        for seconds in [0, 5, 10, 30, 60] {
+            cursor_blink_timeout_names.push(if seconds == 0 {
+                fl!("cursor-blink-never")
+            } else {
+                fl!("notify-seconds", seconds = seconds)
+            });
+            cursor_blink_timeouts.push(seconds);
+        }
 
         let font_name_faces_map = {
             let mut font_name_faces_map = BTreeMap::<_, Vec<_>>::new();
@@ -1092,7 +1786,7 @@ impl Application for App {
             process::exit(1);
         }
 
-        let font_names = font_name_faces_map.keys().cloned().collect();
+        let font_names: Vec<String> = font_name_faces_map.keys().cloned().collect();
 
         let mut font_size_names = Vec::new();
         let mut font_sizes = Vec::new();
@@ -1144,6 +1838,33 @@ impl Application for App {
             zoom_steps.push(zoom_step);
         }
 
+        let mut notify_seconds_names = Vec::new();
+        let mut notify_seconds = Vec::new();
+        for seconds in [5, 10, 30, 60, 300] {
+            notify_seconds_names.push(fl!("notify-seconds", seconds = seconds));
+            notify_seconds.push(seconds);
+        }
+
+        let mut scrollback_lines_names = Vec::new();
+        let mut scrollback_lines = Vec::new();
+        for lines in [1000, 5000, 10_000, 50_000, 100_000] {
+            scrollback_lines_names.push(fl!("scrollback-lines-count", lines = lines));
+            scrollback_lines.push(lines);
+        }
+
+        let with_global_setting = |names: &[String]| {
+            let mut profile_names = Vec::with_capacity(names.len() + 1);
+            profile_names.push(fl!("global-setting"));
+            profile_names.extend_from_slice(names);
+            profile_names
+        };
+        let profile_cursor_shape_names = with_global_setting(&cursor_shape_names);
+        let profile_font_names = with_global_setting(&font_names);
+        let profile_font_size_names = with_global_setting(&font_size_names);
+        let profile_scrollback_lines_names = with_global_setting(&scrollback_lines_names);
+
+        let key_binds = key_binds(&flags.config);
+
         let pane_model = TerminalPaneGrid::new(segmented_button::ModelBuilder::default().build());
         let mut terminal_ids = HashMap::new();
         terminal_ids.insert(pane_model.focus, widget::Id::unique());
@@ -1153,8 +1874,16 @@ impl Application for App {
             pane_model,
             config_handler: flags.config_handler,
             config: flags.config,
-            key_binds: key_binds(),
+            state_handler: flags.state_handler,
+            state: flags.state,
+            key_binds,
             app_themes,
+            bell_mode_names,
+            cursor_shape_names,
+            cursor_blink_interval_names,
+            cursor_blink_intervals,
+            cursor_blink_timeout_names,
+            cursor_blink_timeouts,
             font_names,
             font_size_names,
             font_sizes,
@@ -1167,6 +1896,14 @@ impl Application for App {
             curr_font_stretches: Vec::new(),
             zoom_step_names,
             zoom_steps,
+            notify_seconds_names,
+            notify_seconds,
+            scrollback_lines_names,
+            scrollback_lines,
+            profile_cursor_shape_names,
+            profile_font_names,
+            profile_font_size_names,
+            profile_scrollback_lines_names,
             theme_names_dark: Vec::new(),
             theme_names_light: Vec::new(),
             themes: HashMap::new(),
@@ -1176,6 +1913,7 @@ impl Application for App {
             find: false,
             find_search_id: widget::Id::unique(),
             find_search_value: String::new(),
+            find_vi_forwards_opt: None,
             startup_options: flags.startup_options,
             term_config: flags.term_config,
             term_event_tx_opt: None,
@@ -1185,8 +1923,12 @@ impl Application for App {
             color_scheme_rename_id: widget::Id::unique(),
             color_scheme_tab_model: widget::segmented_button::Model::default(),
             profile_expanded: None,
+            key_bind_editing: None,
+            key_bind_edit_id: widget::Id::unique(),
             show_advanced_font_settings: false,
+            font_fallbacks_value: flags.config.font_fallbacks.join(", "),
             modifiers: Modifiers::empty(),
+            window_focused: true,
         };
 
         app.set_curr_font_weights_and_stretches();
@@ -1195,6 +1937,11 @@ impl Application for App {
         (app, command)
     }
 
+    fn on_close_requested(&self, id: window::Id) -> Option<Message> {
+        // Save the session before the main window goes away
+        (id == window::Id::MAIN).then_some(Message::WindowClose)
+    }
+
     //TODO: currently the first escape unfocuses, and the second calls this function
     fn on_escape(&mut self) -> Command<Message> {
         if self.core.window.show_context {
@@ -1451,6 +2198,9 @@ impl Application for App {
                 if config != self.config {
                     log::info!("update config");
                     //TODO: update syntax theme by clearing tabs, only if needed
+                    if config.font_fallbacks != self.config.font_fallbacks {
+                        self.font_fallbacks_value = config.font_fallbacks.join(", ");
+                    }
                     self.config = config;
                     return self.update_config();
                 }
@@ -1614,6 +2364,7 @@ impl Application for App {
             Message::Drop(None) => {}
             Message::Find(find) => {
                 self.find = find;
+                self.find_vi_forwards_opt = None;
                 if find {
                     if let Some(tab_model) = self.pane_model.active() {
                         let entity = tab_model.active();
@@ -1685,14 +2436,144 @@ impl Application for App {
                     }
                 }
             }
+            Message::KeyBindEdit(action, value) => {
+                let focus = self.key_bind_editing.is_none();
+                self.key_bind_editing = Some((action, value));
+                if focus {
+                    return widget::text_input::focus(self.key_bind_edit_id.clone());
+                }
+            }
+            Message::KeyBindReset(action) => {
+                self.key_bind_editing = None;
+                let defaults = default_key_binds();
+                let mut key_binds = self.config.key_binds.clone();
+                key_binds.retain(|shortcut, action_opt| match action_opt {
+                    // Remove custom shortcuts for this action
+                    Some(key_action) => *key_action != action,
+                    // Restore defaults of this action that were unbound
+                    None => parse_key_bind(shortcut)
+                        .and_then(|key_bind| defaults.get(&key_bind))
+                        .map_or(true, |default_action| *default_action != action),
+                });
+                return self.save_key_binds(key_binds);
+            }
+            Message::KeyBindSubmit => {
+                if let Some((action, value)) = self.key_bind_editing.take() {
+                    let mut new_key_binds = Vec::new();
+                    for shortcut in value.split(',').map(str::trim) {
+                        if shortcut.is_empty() {
+                            continue;
+                        }
+                        match parse_key_bind(shortcut) {
+                            Some(key_bind) => new_key_binds.push(key_bind),
+                            None => {
+                                // Keep editing so the invalid shortcut can be fixed
+                                self.key_bind_editing = Some((action, value));
+                                return Command::none();
+                            }
+                        }
+                    }
+
+                    let defaults = default_key_binds();
+                    let mut key_binds = self.config.key_binds.clone();
+
+                    // Unbind all shortcuts currently assigned to this action
+                    for (key_bind, key_action) in &self.key_binds {
+                        if *key_action == action {
+                            key_binds.retain(|shortcut, _| {
+                                parse_key_bind(shortcut).as_ref() != Some(key_bind)
+                            });
+                            if defaults.contains_key(key_bind) {
+                                key_binds.insert(key_bind.to_string(), None);
+                            }
+                        }
+                    }
+
+                    // Bind the new shortcuts, replacing any previous override of them
+                    for key_bind in new_key_binds {
+                        key_binds.retain(|shortcut, _| {
+                            parse_key_bind(shortcut).as_ref() != Some(&key_bind)
+                        });
+                        if defaults.get(&key_bind) != Some(&action) {
+                            key_binds.insert(key_bind.to_string(), Some(action));
+                        }
+                    }
+
+                    return self.save_key_binds(key_binds);
+                }
+            }
             Message::LaunchUrl(url) => {
                 if let Err(err) = open::that_detached(&url) {
                     log::warn!("failed to open {:?}: {}", url, err);
                 }
             }
+            Message::LetterSpacing(letter_spacing) => {
+                config_set!(letter_spacing, letter_spacing);
+                return self.update_config();
+            }
+            Message::LineHeight(line_height) => {
+                config_set!(line_height_mul_100, line_height);
+                return self.update_config();
+            }
+            Message::MinContrast(min_contrast) => {
+                config_set!(min_contrast_mul_100, min_contrast);
+                return self.update_config();
+            }
             Message::Modifiers(modifiers) => {
                 self.modifiers = modifiers;
             }
+            Message::CursorBlink(cursor_blink) => {
+                config_set!(cursor_blink, cursor_blink);
+                return self.update_config();
+            }
+            Message::CursorBlinkInterval(index) => match self.cursor_blink_intervals.get(index) {
+                Some(interval) => {
+                    config_set!(cursor_blink_interval_ms, *interval);
+                    return self.update_config();
+                }
+                None => {
+                    log::warn!("failed to find cursor blink interval with index {}", index);
+                }
+            },
+            Message::CursorBlinkTick => {
+                // Rerender the cursor line in the new blink phase
+                if let Some(tab_model) = self.pane_model.active() {
+                    let entity = tab_model.active();
+                    if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
+                        let mut terminal = terminal.lock().unwrap();
+                        terminal.needs_update = true;
+                    }
+                }
+            }
+            Message::CursorBlinkTimeout(index) => match self.cursor_blink_timeouts.get(index) {
+                Some(timeout) => {
+                    config_set!(cursor_blink_timeout_seconds, *timeout);
+                    return self.update_config();
+                }
+                None => {
+                    log::warn!("failed to find cursor blink timeout with index {}", index);
+                }
+            },
+            Message::CursorShape(index) => match CursorShape::ALL.get(index) {
+                Some(cursor_shape) => {
+                    config_set!(cursor_shape, *cursor_shape);
+                    return self.update_config();
+                }
+                None => {
+                    log::warn!("failed to find cursor shape with index {}", index);
+                }
+            },
+            Message::NotifyCommandFinished(notify_command_finished) => {
+                config_set!(notify_command_finished, notify_command_finished);
+            }
+            Message::NotifyCommandSeconds(index) => match self.notify_seconds.get(index) {
+                Some(seconds) => {
+                    config_set!(notify_command_seconds, *seconds);
+                }
+                None => {
+                    log::warn!("failed to find notify seconds with index {}", index);
+                }
+            },
             Message::MouseEnter(pane) => {
                 self.pane_model.focus = pane;
                 return self.update_focus();
@@ -1700,6 +2581,12 @@ impl Application for App {
             Message::Opacity(opacity) => {
                 config_set!(opacity, cmp::min(100, opacity));
             }
+            Message::PaddingHorizontal(padding) => {
+                config_set!(padding_horizontal, padding);
+            }
+            Message::PaddingVertical(padding) => {
+                config_set!(padding_vertical, padding);
+            }
             Message::PaneClicked(pane) => {
                 self.pane_model.focus = pane;
                 return self.update_title(Some(pane));
@@ -1765,6 +2652,14 @@ impl Application for App {
                 }
                 return self.update_focus();
             }
+            Message::ProfileBell(profile_id, index) => {
+                if let Some(profile) = self.config.profiles.get_mut(&profile_id) {
+                    if let Some(bell_mode) = BellMode::ALL.get(index) {
+                        profile.bell = *bell_mode;
+                        return self.save_profiles();
+                    }
+                }
+            }
             Message::ProfileCollapse(_profile_id) => {
                 self.profile_expanded = None;
             }
@@ -1774,6 +2669,15 @@ impl Application for App {
                     return self.save_profiles();
                 }
             }
+            Message::ProfileCursorShape(profile_id, index) => {
+                if let Some(profile) = self.config.profiles.get_mut(&profile_id) {
+                    profile.cursor_shape = index
+                        .checked_sub(1)
+                        .and_then(|index| CursorShape::ALL.get(index))
+                        .copied();
+                    return Command::batch([self.save_profiles(), self.update_config()]);
+                }
+            }
             Message::ProfileDirectory(profile_id, text) => {
                 if let Some(profile) = self.config.profiles.get_mut(&profile_id) {
                     profile.working_directory = text;
@@ -1783,6 +2687,24 @@ impl Application for App {
             Message::ProfileExpand(profile_id) => {
                 self.profile_expanded = Some(profile_id);
             }
+            Message::ProfileFont(profile_id, index) => {
+                if let Some(profile) = self.config.profiles.get_mut(&profile_id) {
+                    profile.font_name = index
+                        .checked_sub(1)
+                        .and_then(|index| self.font_names.get(index))
+                        .cloned();
+                    return Command::batch([self.save_profiles(), self.update_config()]);
+                }
+            }
+            Message::ProfileFontSize(profile_id, index) => {
+                if let Some(profile) = self.config.profiles.get_mut(&profile_id) {
+                    profile.font_size = index
+                        .checked_sub(1)
+                        .and_then(|index| self.font_sizes.get(index))
+                        .copied();
+                    return Command::batch([self.save_profiles(), self.update_config()]);
+                }
+            }
             Message::ProfileHold(profile_id, hold) => {
                 if let Some(profile) = self.config.profiles.get_mut(&profile_id) {
                     profile.hold = hold;
@@ -1807,9 +2729,27 @@ impl Application for App {
                 self.profile_expanded = Some(profile_id);
                 return self.save_profiles();
             }
+            Message::ProfileOpacity(profile_id, opacity_opt) => {
+                if let Some(profile) = self.config.profiles.get_mut(&profile_id) {
+                    profile.opacity = opacity_opt.map(|opacity| cmp::min(100, opacity));
+                    return self.save_profiles();
+                }
+            }
             Message::ProfileOpen(profile_id) => {
                 return self.create_and_focus_new_terminal(self.pane_model.focus, Some(profile_id));
             }
+            Message::ProfilePaddingHorizontal(profile_id, padding_opt) => {
+                if let Some(profile) = self.config.profiles.get_mut(&profile_id) {
+                    profile.padding_horizontal = padding_opt;
+                    return self.save_profiles();
+                }
+            }
+            Message::ProfilePaddingVertical(profile_id, padding_opt) => {
+                if let Some(profile) = self.config.profiles.get_mut(&profile_id) {
+                    profile.padding_vertical = padding_opt;
+                    return self.save_profiles();
+                }
+            }
             Message::ProfileRemove(profile_id) => {
                 // Reset matching terminals to default profile
                 for (_pane, tab_model) in self.pane_model.panes.iter() {
@@ -1828,6 +2768,15 @@ impl Application for App {
                 self.config.profiles.remove(&profile_id);
                 return self.save_profiles();
             }
+            Message::ProfileScrollbackLines(profile_id, index) => {
+                if let Some(profile) = self.config.profiles.get_mut(&profile_id) {
+                    profile.scrollback_lines = index
+                        .checked_sub(1)
+                        .and_then(|index| self.scrollback_lines.get(index))
+                        .copied();
+                    return Command::batch([self.save_profiles(), self.update_config()]);
+                }
+            }
             Message::ProfileSyntaxTheme(profile_id, color_scheme_kind, theme_i) => {
                 match self
                     .theme_names(color_scheme_kind)
@@ -1858,6 +2807,47 @@ impl Application for App {
                     return self.save_profiles();
                 }
             }
+            Message::PromptNext(entity_opt) => {
+                if let Some(tab_model) = self.pane_model.active() {
+                    let entity = entity_opt.unwrap_or_else(|| tab_model.active());
+                    if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
+                        let mut terminal = terminal.lock().unwrap();
+                        terminal.jump_to_prompt(true);
+                    }
+                }
+                return self.update_focus();
+            }
+            Message::PromptPrevious(entity_opt) => {
+                if let Some(tab_model) = self.pane_model.active() {
+                    let entity = entity_opt.unwrap_or_else(|| tab_model.active());
+                    if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
+                        let mut terminal = terminal.lock().unwrap();
+                        terminal.jump_to_prompt(false);
+                    }
+                }
+                return self.update_focus();
+            }
+            Message::RestoreSession(restore_session) => {
+                config_set!(restore_session, restore_session);
+            }
+            Message::ScrollLines(scroll_lines) => {
+                config_set!(scroll_lines, scroll_lines);
+            }
+            Message::ScrollPixels(scroll_pixels) => {
+                config_set!(scroll_pixels_mul_100, scroll_pixels);
+            }
+            Message::ScrollShiftPage(scroll_shift_page) => {
+                config_set!(scroll_shift_page, scroll_shift_page);
+            }
+            Message::ScrollbackLines(index) => match self.scrollback_lines.get(index) {
+                Some(lines) => {
+                    config_set!(scrollback_lines, *lines);
+                    return self.update_config();
+                }
+                None => {
+                    log::warn!("failed to find scrollback lines with index {}", index);
+                }
+            },
             Message::SelectAll(entity_opt) => {
                 if let Some(tab_model) = self.pane_model.active() {
                     let entity = entity_opt.unwrap_or_else(|| tab_model.active());
@@ -1868,12 +2858,57 @@ impl Application for App {
                 }
                 return self.update_focus();
             }
+            Message::SelectCommandOutput(entity_opt) => {
+                if let Some(tab_model) = self.pane_model.active() {
+                    let entity = entity_opt.unwrap_or_else(|| tab_model.active());
+                    if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
+                        let mut terminal = terminal.lock().unwrap();
+                        terminal.select_command_output();
+                    }
+                }
+                return self.update_focus();
+            }
             Message::ShowHeaderBar(show_headerbar) => {
                 if show_headerbar != self.config.show_headerbar {
                     config_set!(show_headerbar, show_headerbar);
                     return self.update_config();
                 }
             }
+            Message::ViSearch(forwards) => {
+                self.find = true;
+                self.find_vi_forwards_opt = Some(forwards);
+                self.find_search_value.clear();
+                return self.update_focus();
+            }
+            Message::ViSearchSubmit => {
+                if let Some(forwards) = self.find_vi_forwards_opt {
+                    if !self.find_search_value.is_empty() {
+                        if let Some(tab_model) = self.pane_model.active() {
+                            let entity = tab_model.active();
+                            if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
+                                let mut terminal = terminal.lock().unwrap();
+                                terminal.search(&self.find_search_value, forwards);
+                            }
+                        }
+                    }
+                }
+
+                // Like vi, return to the terminal after searching
+                return self.update(Message::Find(false));
+            }
+            Message::FontFallbacks(value) => {
+                let font_fallbacks: Vec<String> = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(String::from)
+                    .collect();
+                self.font_fallbacks_value = value;
+                if font_fallbacks != self.config.font_fallbacks {
+                    config_set!(font_fallbacks, font_fallbacks);
+                    return self.update_config();
+                }
+            }
             Message::UseBrightBold(use_bright_bold) => {
                 if use_bright_bold != self.config.use_bright_bold {
                     config_set!(use_bright_bold, use_bright_bold);
@@ -1952,6 +2987,7 @@ impl Application for App {
                             self.pane_model.focus = sibling;
                         } else {
                             //Last pane, closing window
+                            self.clear_session();
                             return window::close(window::Id::MAIN);
                         }
                     }
@@ -2040,7 +3076,39 @@ impl Application for App {
             Message::TermEvent(pane, entity, event) => {
                 match event {
                     TermEvent::Bell => {
-                        //TODO: audible or visible bell options?
+                        if let Some(tab_model) = self.pane_model.panes.get_mut(pane) {
+                            let mut bell_mode_opt = None;
+                            if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
+                                let mut terminal = terminal.lock().unwrap();
+                                let bell_mode = terminal
+                                    .profile_id_opt
+                                    .and_then(|profile_id| self.config.profiles.get(&profile_id))
+                                    .map_or_else(BellMode::default, |profile| profile.bell);
+                                if bell_mode != BellMode::None
+                                    && terminal.bell(bell_mode == BellMode::Visual)
+                                {
+                                    bell_mode_opt = Some(bell_mode);
+                                }
+                            }
+                            match bell_mode_opt {
+                                Some(BellMode::Urgent) if !self.window_focused => {
+                                    return window::request_user_attention(
+                                        window::Id::MAIN,
+                                        Some(window::UserAttention::Informational),
+                                    );
+                                }
+                                Some(BellMode::TabIndicator) if entity != tab_model.active() => {
+                                    tab_model.icon_set(
+                                        entity,
+                                        icon_cache_get(
+                                            "preferences-system-notifications-symbolic",
+                                            16,
+                                        ),
+                                    );
+                                }
+                                _ => {}
+                            }
+                        }
                     }
                     TermEvent::ClipboardLoad(kind, callback) => {
                         match kind {
@@ -2071,14 +3139,20 @@ impl Application for App {
                         if let Some(tab_model) = self.pane_model.panes.get(pane) {
                             if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
                                 let terminal = terminal.lock().unwrap();
-                                let rgb = terminal.colors()[index].unwrap_or_default();
+                                let rgb = terminal.render_colors()[index].unwrap_or_default();
                                 let text = f(rgb);
                                 terminal.input_no_scroll(text.into_bytes());
                             }
                         }
                     }
                     TermEvent::CursorBlinkingChange => {
-                        //TODO: should we blink the cursor?
+                        // Start the new style in the visible phase
+                        if let Some(tab_model) = self.pane_model.panes.get(pane) {
+                            if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
+                                let mut terminal = terminal.lock().unwrap();
+                                terminal.reset_cursor_blink();
+                            }
+                        }
                     }
                     TermEvent::Exit => {
                         return self.update(Message::TabClose(Some(entity)));
@@ -2136,6 +3210,39 @@ impl Application for App {
                             if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
                                 let mut terminal = terminal.lock().unwrap();
                                 terminal.needs_update = true;
+
+                                let tab_title = tab_model.text(entity).unwrap_or_default();
+                                let mut commands = Vec::new();
+                                if let Some(notification) = terminal.take_notification() {
+                                    commands.push(self.notify(
+                                        notification.title.unwrap_or_else(|| tab_title.to_string()),
+                                        notification.body,
+                                    ));
+                                }
+
+                                let focused = self.window_focused
+                                    && pane == self.pane_model.focus
+                                    && entity == tab_model.active();
+                                for command in terminal.take_finished_commands() {
+                                    if !self.config.notify_command_finished
+                                        || focused
+                                        || command.duration.as_secs()
+                                            < u64::from(self.config.notify_command_seconds)
+                                    {
+                                        continue;
+                                    }
+                                    let seconds = command.duration.as_secs();
+                                    let body = match command.status {
+                                        Some(status) if status != 0 => fl!(
+                                            "command-failed",
+                                            status = status,
+                                            seconds = seconds
+                                        ),
+                                        _ => fl!("command-finished", seconds = seconds),
+                                    };
+                                    commands.push(self.notify(tab_title.to_string(), body));
+                                }
+                                return Command::batch(commands);
                             }
                         }
                     }
@@ -2145,6 +3252,11 @@ impl Application for App {
                 }
             }
             Message::TermEventTx(term_event_tx) => {
+                // Only restore the session for the first channel, not after a reset
+                let restore_session = self.term_event_tx_opt.is_none()
+                    && self.config.restore_session
+                    && self.startup_options.is_none();
+
                 // Check if the terminal event channel was reset
                 if self.term_event_tx_opt.is_some() {
                     // Close tabs using old terminal event channel
@@ -2170,9 +3282,25 @@ impl Application for App {
                 // Set new terminal event channel
                 self.term_event_tx_opt = Some(term_event_tx);
 
+                if restore_session {
+                    if let Some(session) = self.state.session.clone() {
+                        return self.restore_session(session);
+                    }
+                }
+
                 // Spawn first tab
                 return self.update(Message::TabNew);
             }
+            Message::ToggleViMode(entity_opt) => {
+                if let Some(tab_model) = self.pane_model.active() {
+                    let entity = entity_opt.unwrap_or_else(|| tab_model.active());
+                    if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
+                        let mut terminal = terminal.lock().unwrap();
+                        terminal.toggle_vi_mode();
+                    }
+                }
+                return self.update_focus();
+            }
             Message::ToggleContextPage(context_page) => {
                 if self.context_page == context_page {
                     self.core.window.show_context = !self.core.window.show_context;
@@ -2182,6 +3310,7 @@ impl Application for App {
                 }
 
                 // Extra work to do to prepare context pages
+                self.key_bind_editing = None;
                 if let ContextPage::ColorSchemes(color_scheme_kind) = self.context_page {
                     self.color_scheme_errors.clear();
                     self.color_scheme_expanded = None;
@@ -2212,8 +3341,12 @@ impl Application for App {
                 config_set!(default_profile, default.then_some(profile_id));
             }
             Message::WindowClose => {
+                self.save_session();
                 return window::close(window::Id::MAIN);
             }
+            Message::WindowFocused(focused) => {
+                self.window_focused = focused;
+            }
             Message::WindowNew => match env::current_exe() {
                 Ok(exe) => match process::Command::new(&exe).spawn() {
                     Ok(_child) => {}
@@ -2248,6 +3381,7 @@ impl Application for App {
         Some(match self.context_page {
             ContextPage::About => self.about(),
             ContextPage::ColorSchemes(color_scheme_kind) => self.color_schemes(color_scheme_kind),
+            ContextPage::KeyBinds => self.key_bindings(),
             ContextPage::Profiles => self.profiles(),
             ContextPage::Settings => self.settings(),
         })
@@ -2302,24 +3436,32 @@ impl Application for App {
                 .cloned()
                 .unwrap_or_else(widget::Id::unique);
             if let Some(terminal) = tab_model.data::<Mutex<Terminal>>(entity) {
+                let (context_menu, profile_id_opt) = {
+                    let terminal = terminal.lock().unwrap();
+                    (terminal.context_menu, terminal.profile_id_opt)
+                };
+                let (padding_horizontal, padding_vertical) =
+                    self.config.profile_padding(profile_id_opt);
+
                 let mut terminal_box = terminal_box(terminal)
                     .id(terminal_id)
                     .on_context_menu(move |position_opt| {
                         Message::TabContextMenu(pane, position_opt)
                     })
                     .on_middle_click(move || Message::MiddleClick(pane, Some(entity_middle_click)))
-                    .opacity(self.config.opacity_ratio())
-                    .padding(space_xxs);
+                    .on_open_url(Message::LaunchUrl)
+                    .on_vi_search(Message::ViSearch)
+                    .opacity(self.config.opacity_ratio(profile_id_opt))
+                    .padding([padding_vertical, padding_horizontal])
+                    .scroll_lines(f32::from(self.config.scroll_lines))
+                    .scroll_pixels_multiplier(f32::from(self.config.scroll_pixels_mul_100) / 100.0)
+                    .scroll_shift_page(self.config.scroll_shift_page)
+                    .key_binds(&self.key_binds);
 
                 if self.config.focus_follow_mouse {
                     terminal_box = terminal_box.on_mouse_enter(move || Message::MouseEnter(pane));
                 }
 
-                let context_menu = {
-                    let terminal = terminal.lock().unwrap();
-                    terminal.context_menu
-                };
-
                 let tab_element: Element<'_, Message> = match context_menu {
                     Some(point) => widget::popover(terminal_box.context_menu(point))
                         .popup(menu::context_menu(&self.config, &self.key_binds, entity))
@@ -2340,7 +3482,9 @@ impl Application for App {
                 .on_input(Message::FindSearchValueChanged)
                 // This is inverted for ease of use, usually in terminals you want to search
                 // upwards, which is FindPrevious
-                .on_submit(if self.modifiers.contains(Modifiers::SHIFT) {
+                .on_submit(if self.find_vi_forwards_opt.is_some() {
+                    Message::ViSearchSubmit
+                } else if self.modifiers.contains(Modifiers::SHIFT) {
                     Message::FindNext
                 } else {
                     Message::FindPrevious
@@ -2430,6 +3574,8 @@ impl Application for App {
                 Event::Mouse(MouseEvent::ButtonReleased(MouseButton::Left)) => {
                     Some(Message::CopyPrimary(None))
                 }
+                Event::Window(_id, window::Event::Focused) => Some(Message::WindowFocused(true)),
+                Event::Window(_id, window::Event::Unfocused) => Some(Message::WindowFocused(false)),
                 _ => None,
             }),
             subscription::channel(
@@ -2485,6 +3631,12 @@ impl Application for App {
                 Some(dialog) => dialog.subscription(),
                 None => subscription::Subscription::none(),
             },
+            match self.cursor_blink_interval() {
+                Some(interval) => {
+                    iced::time::every(interval).map(|_instant| Message::CursorBlinkTick)
+                }
+                None => subscription::Subscription::none(),
+            },
         ])
     }
 }
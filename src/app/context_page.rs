@@ -4,6 +4,7 @@ use crate::{config::color_scheme::ColorSchemeKind, fl};
 pub enum ContextPage {
     About,
     ColorSchemes(ColorSchemeKind),
+    KeyBinds,
     Profiles,
     Settings,
 }
@@ -13,6 +14,7 @@ impl ContextPage {
         match self {
             Self::About => String::new(),
             Self::ColorSchemes(_color_scheme_kind) => fl!("color-schemes"),
+            Self::KeyBinds => fl!("keyboard-shortcuts"),
             Self::Profiles => fl!("profiles"),
             Self::Settings => fl!("settings"),
         }
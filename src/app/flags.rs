@@ -1,12 +1,14 @@
 use alacritty_terminal::{term, tty};
 use cosmic::cosmic_config;
 
-use crate::config::config::Config;
+use crate::config::{config::Config, session::SessionState};
 
 #[derive(Clone, Debug)]
 pub struct Flags {
     pub(crate) config_handler: Option<cosmic_config::Config>,
     pub(crate) config: Config,
+    pub(crate) state_handler: Option<cosmic_config::Config>,
+    pub(crate) state: SessionState,
     pub(crate) startup_options: Option<tty::Options>,
     pub(crate) term_config: term::Config,
 }
@@ -37,6 +37,11 @@ pub enum Message {
     Copy(Option<segmented_button::Entity>),
     CopyOrSigint(Option<segmented_button::Entity>),
     CopyPrimary(Option<segmented_button::Entity>),
+    CursorBlink(bool),
+    CursorBlinkInterval(usize),
+    CursorBlinkTick,
+    CursorBlinkTimeout(usize),
+    CursorShape(usize),
     DefaultBoldFontWeight(usize),
     DefaultDimFontWeight(usize),
     DefaultFont(usize),
@@ -50,13 +55,24 @@ pub enum Message {
     FindNext,
     FindPrevious,
     FindSearchValueChanged(String),
+    FontFallbacks(String),
     MiddleClick(pane_grid::Pane, Option<segmented_button::Entity>),
     FocusFollowMouse(bool),
     Key(Modifiers, Key),
+    KeyBindEdit(Action, String),
+    KeyBindReset(Action),
+    KeyBindSubmit,
     LaunchUrl(String),
+    LetterSpacing(i16),
+    LineHeight(u16),
+    MinContrast(u16),
     Modifiers(Modifiers),
     MouseEnter(pane_grid::Pane),
+    NotifyCommandFinished(bool),
+    NotifyCommandSeconds(usize),
     Opacity(u8),
+    PaddingHorizontal(u16),
+    PaddingVertical(u16),
     PaneClicked(pane_grid::Pane),
     PaneDragged(pane_grid::DragEvent),
     PaneFocusAdjacent(pane_grid::Direction),
@@ -66,18 +82,34 @@ pub enum Message {
     Paste(Option<segmented_button::Entity>),
     PastePrimary(Option<segmented_button::Entity>),
     PasteValue(Option<segmented_button::Entity>, String),
+    ProfileBell(ProfileId, usize),
     ProfileCollapse(ProfileId),
     ProfileCommand(ProfileId, String),
+    ProfileCursorShape(ProfileId, usize),
     ProfileDirectory(ProfileId, String),
     ProfileExpand(ProfileId),
+    ProfileFont(ProfileId, usize),
+    ProfileFontSize(ProfileId, usize),
     ProfileHold(ProfileId, bool),
     ProfileName(ProfileId, String),
     ProfileNew,
+    ProfileOpacity(ProfileId, Option<u8>),
     ProfileOpen(ProfileId),
+    ProfilePaddingHorizontal(ProfileId, Option<u16>),
+    ProfilePaddingVertical(ProfileId, Option<u16>),
     ProfileRemove(ProfileId),
+    ProfileScrollbackLines(ProfileId, usize),
     ProfileSyntaxTheme(ProfileId, ColorSchemeKind, usize),
     ProfileTabTitle(ProfileId, String),
+    PromptNext(Option<segmented_button::Entity>),
+    PromptPrevious(Option<segmented_button::Entity>),
+    RestoreSession(bool),
+    ScrollLines(u16),
+    ScrollPixels(u16),
+    ScrollShiftPage(bool),
+    ScrollbackLines(usize),
     SelectAll(Option<segmented_button::Entity>),
+    SelectCommandOutput(Option<segmented_button::Entity>),
     ShowAdvancedFontSettings(bool),
     ShowHeaderBar(bool),
     SyntaxTheme(ColorSchemeKind, usize),
@@ -94,9 +126,13 @@ pub enum Message {
     TermEvent(pane_grid::Pane, segmented_button::Entity, TermEvent),
     TermEventTx(mpsc::Sender<(pane_grid::Pane, segmented_button::Entity, TermEvent)>),
     ToggleContextPage(ContextPage),
+    ToggleViMode(Option<segmented_button::Entity>),
     UpdateDefaultProfile((bool, ProfileId)),
     UseBrightBold(bool),
+    ViSearch(bool),
+    ViSearchSubmit,
     WindowClose,
+    WindowFocused(bool),
     WindowNew,
     ZoomIn,
     ZoomOut,
@@ -125,6 +125,12 @@ pub struct ColorScheme {
         serialize_with = "ser_color_opt",
         skip_serializing_if = "Option::is_none"
     )]
+    pub cursor_text: Option<HexColor>,
+    #[serde(
+        deserialize_with = "de_color_opt",
+        serialize_with = "ser_color_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub bright_foreground: Option<HexColor>,
     #[serde(
         deserialize_with = "de_color_opt",
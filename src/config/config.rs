@@ -1,12 +1,16 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 use cosmic::cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
-use cosmic_text::{Metrics, Stretch, Weight};
+use cosmic_text::{Family, Metrics, Stretch, Weight};
+use hex_color::HexColor;
 use serde::{Deserialize, Serialize};
 
-use std::collections::BTreeMap;
-use std::sync::OnceLock;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, OnceLock};
 
+use crate::app::action::Action;
+use crate::fl;
 use crate::localization::LANGUAGE_SORTER;
 
 use super::{
@@ -16,19 +20,67 @@ use super::{
     profile::{Profile, ProfileId},
 };
 
+/// Cursor shape used until a program sets another one
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum CursorShape {
+    #[default]
+    Block,
+    Underline,
+    Beam,
+}
+
+impl CursorShape {
+    pub const ALL: &'static [Self] = &[Self::Block, Self::Underline, Self::Beam];
+
+    pub fn title(&self) -> String {
+        match self {
+            Self::Block => fl!("cursor-block"),
+            Self::Underline => fl!("cursor-underline"),
+            Self::Beam => fl!("cursor-beam"),
+        }
+    }
+}
+
 #[derive(Clone, CosmicConfigEntry, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Config {
     pub app_theme: AppTheme,
     pub color_schemes_dark: BTreeMap<ColorSchemeId, ColorScheme>,
     pub color_schemes_light: BTreeMap<ColorSchemeId, ColorScheme>,
     pub font_name: String,
+    /// Fonts to try in order for characters missing from the main font
+    pub font_fallbacks: Vec<String>,
     pub font_size: u16,
     pub font_weight: u16,
     pub dim_font_weight: u16,
     pub bold_font_weight: u16,
     pub font_stretch: u16,
     pub font_size_zoom_step_mul_100: u16,
+    /// Line height as a multiple of the font size, times 100
+    pub line_height_mul_100: u16,
+    /// Extra pixels added to the width of each cell
+    pub letter_spacing: i16,
+    /// Pixels between the text and the left and right edges of a terminal
+    pub padding_horizontal: u16,
+    /// Pixels between the text and the top and bottom edges of a terminal
+    pub padding_vertical: u16,
+    pub cursor_shape: CursorShape,
+    /// Blink the cursor until a program changes it
+    pub cursor_blink: bool,
+    /// Time the cursor stays on and off while blinking
+    pub cursor_blink_interval_ms: u32,
+    /// Stop blinking after this long without typing, 0 to blink forever
+    pub cursor_blink_timeout_seconds: u32,
     pub opacity: u8,
+    /// Minimum contrast ratio between text and its background, times 100, 100 turns it off
+    pub min_contrast_mul_100: u16,
+    /// Lines of history kept by each terminal
+    pub scrollback_lines: u32,
+    /// Lines scrolled for each notch of a mouse wheel
+    pub scroll_lines: u16,
+    /// Multiplier of pixel scrolling from touchpads, times 100
+    pub scroll_pixels_mul_100: u16,
+    /// Scroll a page for each notch of a mouse wheel while Shift is held
+    pub scroll_shift_page: bool,
     pub profiles: BTreeMap<ProfileId, Profile>,
     pub show_headerbar: bool,
     pub use_bright_bold: bool,
@@ -36,6 +88,14 @@ pub struct Config {
     pub syntax_theme_light: String,
     pub focus_follow_mouse: bool,
     pub default_profile: Option<ProfileId>,
+    /// Reopen the panes and tabs from the last session at startup
+    pub restore_session: bool,
+    /// Send a notification when a command finishes in a tab that is not focused
+    pub notify_command_finished: bool,
+    /// Minimum run time of a command for [`Config::notify_command_finished`]
+    pub notify_command_seconds: u32,
+    /// Shortcut overrides on top of the default key bindings, `None` unbinds a shortcut
+    pub key_binds: BTreeMap<String, Option<Action>>,
 }
 
 impl Default for Config {
@@ -45,24 +105,55 @@ impl Default for Config {
             bold_font_weight: Weight::BOLD.0,
             color_schemes_dark: BTreeMap::new(),
             color_schemes_light: BTreeMap::new(),
+            cursor_blink: false,
+            cursor_blink_interval_ms: 750,
+            cursor_blink_timeout_seconds: 5,
+            cursor_shape: CursorShape::default(),
             dim_font_weight: Weight::NORMAL.0,
             focus_follow_mouse: false,
+            font_fallbacks: Vec::new(),
             font_name: "Fira Mono".to_string(),
             font_size: 14,
             font_size_zoom_step_mul_100: 100,
             font_stretch: Stretch::Normal.to_number(),
             font_weight: Weight::NORMAL.0,
+            letter_spacing: 0,
+            line_height_mul_100: 140,
+            min_contrast_mul_100: 100,
             opacity: 100,
+            padding_horizontal: 4,
+            padding_vertical: 4,
             profiles: BTreeMap::new(),
+            scrollback_lines: 10_000,
+            scroll_lines: 6,
+            scroll_pixels_mul_100: 600,
+            scroll_shift_page: true,
             show_headerbar: true,
             syntax_theme_dark: COSMIC_THEME_DARK.to_string(),
             syntax_theme_light: COSMIC_THEME_LIGHT.to_string(),
             use_bright_bold: false,
             default_profile: None,
+            restore_session: false,
+            notify_command_finished: false,
+            notify_command_seconds: 10,
+            key_binds: BTreeMap::new(),
         }
     }
 }
 
+// Font families in attributes borrow their name for the whole program, so each name used by a
+// profile is leaked once
+fn static_font_name(font_name: &str) -> &'static str {
+    static FONT_NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut font_names = FONT_NAMES.lock().unwrap();
+    if let Some(&static_name) = font_names.get(font_name) {
+        return static_name;
+    }
+    let static_name: &'static str = Box::leak(font_name.into());
+    font_names.insert(static_name);
+    static_name
+}
+
 impl Config {
     pub fn color_schemes(
         &self,
@@ -125,12 +216,78 @@ impl Config {
     // Calculate metrics from font size
     pub fn metrics(&self, zoom_adj: i8) -> Metrics {
         let font_size = self.font_size_adjusted(zoom_adj);
-        let line_height = (font_size * 1.4).ceil();
+        let line_height_mul = f32::from(self.line_height_mul_100) / 100.0;
+        let line_height = (font_size * line_height_mul).ceil().max(1.0);
         Metrics::new(font_size, line_height)
     }
 
-    pub fn opacity_ratio(&self) -> f32 {
-        f32::from(self.opacity) / 100.0
+    pub fn min_contrast(&self) -> f64 {
+        f64::from(self.min_contrast_mul_100.max(100)) / 100.0
+    }
+
+    pub fn opacity_ratio(&self, profile_id_opt: Option<ProfileId>) -> f32 {
+        f32::from(self.profile_opacity(profile_id_opt)) / 100.0
+    }
+
+    // Get the font family used by a profile, the global font is the monospace family
+    pub fn font_family(&self, profile_id_opt: Option<ProfileId>) -> Family<'static> {
+        match profile_id_opt
+            .and_then(|profile_id| self.profiles.get(&profile_id))
+            .and_then(|profile| profile.font_name.as_ref())
+            .filter(|font_name| **font_name != self.font_name)
+        {
+            Some(font_name) => Family::Name(static_font_name(font_name)),
+            None => Family::Monospace,
+        }
+    }
+
+    // Get the opacity of a profile, without cloning the settings like profile_config
+    pub fn profile_opacity(&self, profile_id_opt: Option<ProfileId>) -> u8 {
+        profile_id_opt
+            .and_then(|profile_id| self.profiles.get(&profile_id))
+            .and_then(|profile| profile.opacity)
+            .unwrap_or(self.opacity)
+    }
+
+    // Get the horizontal and vertical padding of a profile, without cloning the settings like
+    // profile_config
+    pub fn profile_padding(&self, profile_id_opt: Option<ProfileId>) -> (u16, u16) {
+        let profile_opt = profile_id_opt.and_then(|profile_id| self.profiles.get(&profile_id));
+        (
+            profile_opt
+                .and_then(|profile| profile.padding_horizontal)
+                .unwrap_or(self.padding_horizontal),
+            profile_opt
+                .and_then(|profile| profile.padding_vertical)
+                .unwrap_or(self.padding_vertical),
+        )
+    }
+
+    // Get the settings with the appearance overrides of a profile applied
+    pub fn profile_config(&self, profile_id_opt: Option<ProfileId>) -> Cow<'_, Self> {
+        let Some(profile) = profile_id_opt
+            .and_then(|profile_id| self.profiles.get(&profile_id))
+            .filter(|profile| profile.has_overrides())
+        else {
+            return Cow::Borrowed(self);
+        };
+
+        let mut config = self.clone();
+        if let Some(font_name) = &profile.font_name {
+            config.font_name.clone_from(font_name);
+        }
+        config.font_size = profile.font_size.unwrap_or(config.font_size);
+        config.font_weight = profile.font_weight.unwrap_or(config.font_weight);
+        config.bold_font_weight = profile.bold_font_weight.unwrap_or(config.bold_font_weight);
+        config.opacity = profile.opacity.unwrap_or(config.opacity);
+        config.padding_horizontal = profile
+            .padding_horizontal
+            .unwrap_or(config.padding_horizontal);
+        config.padding_vertical = profile.padding_vertical.unwrap_or(config.padding_vertical);
+        config.cursor_shape = profile.cursor_shape.unwrap_or(config.cursor_shape);
+        config.cursor_blink = profile.cursor_blink.unwrap_or(config.cursor_blink);
+        config.scrollback_lines = profile.scrollback_lines.unwrap_or(config.scrollback_lines);
+        Cow::Owned(config)
     }
 
     // Get a sorted and adjusted for duplicates list of profile names and ids
@@ -168,6 +325,18 @@ impl Config {
         (theme_name, color_scheme_kind)
     }
 
+    // Get the cursor text color of the current syntax theme, only custom color schemes have one
+    pub fn syntax_theme_cursor_text(&self, profile_id_opt: Option<ProfileId>) -> Option<HexColor> {
+        let (theme_name, color_scheme_kind) = self.syntax_theme(profile_id_opt);
+        let (_, color_scheme_id) = self
+            .color_scheme_names(color_scheme_kind)
+            .into_iter()
+            .find(|(name, _)| name == &theme_name)?;
+        self.color_schemes(color_scheme_kind)
+            .get(&color_scheme_id)?
+            .cursor_text
+    }
+
     pub fn typed_font_stretch(&self) -> Stretch {
         macro_rules! populate_num_typed_map {
             ($($stretch:ident,)+) => {
@@ -1,3 +1,4 @@
 pub const CONFIG_VERSION: u64 = 1;
+pub const SESSION_STATE_VERSION: u64 = 1;
 pub const COSMIC_THEME_DARK: &str = "COSMIC Dark";
 pub const COSMIC_THEME_LIGHT: &str = "COSMIC Light";
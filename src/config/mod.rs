@@ -3,3 +3,4 @@ pub mod color_scheme;
 pub mod config;
 pub mod constants;
 pub mod profile;
+pub mod session;
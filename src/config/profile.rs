@@ -2,12 +2,42 @@ use serde::{Deserialize, Serialize};
 
 use crate::fl;
 
-use super::constants::{COSMIC_THEME_DARK, COSMIC_THEME_LIGHT};
+use super::{
+    config::CursorShape,
+    constants::{COSMIC_THEME_DARK, COSMIC_THEME_LIGHT},
+};
 
 #[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 #[serde(transparent)]
 pub struct ProfileId(pub u64);
 
+/// What happens when the terminal rings the bell
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum BellMode {
+    /// Ignore the bell, as before bell modes existed
+    #[default]
+    None,
+    /// Briefly flash the terminal
+    Visual,
+    /// Request attention for the window when it is not focused
+    Urgent,
+    /// Mark the tab when it is in the background
+    TabIndicator,
+}
+
+impl BellMode {
+    pub const ALL: &'static [Self] = &[Self::None, Self::Visual, Self::Urgent, Self::TabIndicator];
+
+    pub fn title(&self) -> String {
+        match self {
+            Self::None => fl!("bell-none"),
+            Self::Visual => fl!("bell-visual"),
+            Self::Urgent => fl!("bell-urgent"),
+            Self::TabIndicator => fl!("bell-tab-indicator"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Profile {
     pub name: String,
@@ -23,6 +53,45 @@ pub struct Profile {
     pub working_directory: String,
     #[serde(default)]
     pub hold: bool,
+    #[serde(default)]
+    pub bell: BellMode,
+    // Overrides of the global appearance settings, `None` uses the global setting
+    #[serde(default)]
+    pub font_name: Option<String>,
+    #[serde(default)]
+    pub font_size: Option<u16>,
+    #[serde(default)]
+    pub font_weight: Option<u16>,
+    #[serde(default)]
+    pub bold_font_weight: Option<u16>,
+    #[serde(default)]
+    pub opacity: Option<u8>,
+    #[serde(default)]
+    pub padding_horizontal: Option<u16>,
+    #[serde(default)]
+    pub padding_vertical: Option<u16>,
+    #[serde(default)]
+    pub cursor_shape: Option<CursorShape>,
+    #[serde(default)]
+    pub cursor_blink: Option<bool>,
+    #[serde(default)]
+    pub scrollback_lines: Option<u32>,
+}
+
+impl Profile {
+    /// Returns true if the profile overrides any global appearance setting
+    pub fn has_overrides(&self) -> bool {
+        self.font_name.is_some()
+            || self.font_size.is_some()
+            || self.font_weight.is_some()
+            || self.bold_font_weight.is_some()
+            || self.opacity.is_some()
+            || self.padding_horizontal.is_some()
+            || self.padding_vertical.is_some()
+            || self.cursor_shape.is_some()
+            || self.cursor_blink.is_some()
+            || self.scrollback_lines.is_some()
+    }
 }
 
 impl Default for Profile {
@@ -35,6 +104,17 @@ impl Default for Profile {
             tab_title: String::new(),
             working_directory: String::new(),
             hold: false,
+            bell: BellMode::default(),
+            font_name: None,
+            font_size: None,
+            font_weight: None,
+            bold_font_weight: None,
+            opacity: None,
+            padding_horizontal: None,
+            padding_vertical: None,
+            cursor_shape: None,
+            cursor_blink: None,
+            scrollback_lines: None,
         }
     }
 }
@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use cosmic::cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use cosmic::widget::pane_grid;
+use serde::{Deserialize, Serialize};
+
+use std::path::PathBuf;
+
+use super::profile::ProfileId;
+
+/// Application state that is not user configuration, stored separately from [`Config`]
+///
+/// [`Config`]: super::config::Config
+#[derive(Clone, CosmicConfigEntry, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SessionState {
+    pub session: Option<Session>,
+}
+
+/// Snapshot of the window layout written on close
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Session {
+    pub root: SessionPane,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum SessionPane {
+    Split {
+        axis: SessionAxis,
+        ratio: f32,
+        a: Box<SessionPane>,
+        b: Box<SessionPane>,
+    },
+    Tabs {
+        tabs: Vec<SessionTab>,
+        active: usize,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum SessionAxis {
+    Horizontal,
+    Vertical,
+}
+
+impl From<pane_grid::Axis> for SessionAxis {
+    fn from(axis: pane_grid::Axis) -> Self {
+        match axis {
+            pane_grid::Axis::Horizontal => Self::Horizontal,
+            pane_grid::Axis::Vertical => Self::Vertical,
+        }
+    }
+}
+
+impl From<SessionAxis> for pane_grid::Axis {
+    fn from(axis: SessionAxis) -> Self {
+        match axis {
+            SessionAxis::Horizontal => Self::Horizontal,
+            SessionAxis::Vertical => Self::Vertical,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SessionTab {
+    pub profile_id: Option<ProfileId>,
+    pub tab_title_override: Option<String>,
+    pub working_directory: Option<PathBuf>,
+}
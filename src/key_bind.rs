@@ -1,11 +1,12 @@
 use cosmic::widget::menu::key_bind::{KeyBind, Modifier};
 use cosmic::{iced::keyboard::Key, iced_core::keyboard::key::Named};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::app::action::Action;
+use crate::config::config::Config;
+use crate::fl;
 
-//TODO: load from config
-pub fn key_binds() -> HashMap<KeyBind, Action> {
+pub fn default_key_binds() -> HashMap<KeyBind, Action> {
     let mut key_binds = HashMap::new();
 
     macro_rules! bind {
@@ -32,6 +33,11 @@ pub fn key_binds() -> HashMap<KeyBind, Action> {
     bind!([Shift], Key::Named(Named::Insert), PastePrimary);
     bind!([Ctrl, Shift], Key::Character("W".into()), TabClose);
     bind!([Ctrl], Key::Character(",".into()), Settings);
+    bind!([Ctrl, Shift], Key::Named(Named::Space), ToggleViMode);
+
+    // Ctrl+Shift+PageUp and Ctrl+Shift+PageDown jump between shell prompts
+    bind!([Ctrl, Shift], Key::Named(Named::PageUp), PromptPrevious);
+    bind!([Ctrl, Shift], Key::Named(Named::PageDown), PromptNext);
 
     // Ctrl+Alt+D splits horizontally, Ctrl+Alt+R splits vertically, Ctrl+Shift+X maximizes split
     //TODO: Adjust bindings as desired by UX
@@ -77,3 +83,155 @@ pub fn key_binds() -> HashMap<KeyBind, Action> {
 
     key_binds
 }
+
+// Default key bindings with the overrides from the config applied on top
+pub fn key_binds(config: &Config) -> HashMap<KeyBind, Action> {
+    let mut key_binds = default_key_binds();
+    for (shortcut, action_opt) in &config.key_binds {
+        let Some(key_bind) = parse_key_bind(shortcut) else {
+            log::warn!("failed to parse key binding {:?}", shortcut);
+            continue;
+        };
+        // A shortcut mapped to None unbinds the default
+        match action_opt {
+            Some(action) => {
+                key_binds.insert(key_bind, *action);
+            }
+            None => {
+                key_binds.remove(&key_bind);
+            }
+        }
+    }
+    key_binds
+}
+
+// Describe custom shortcuts that cannot be parsed or that collide with another binding
+pub fn key_bind_conflicts(config: &Config) -> Vec<String> {
+    let defaults = default_key_binds();
+    let mut seen = HashSet::new();
+    let mut conflicts = Vec::new();
+    for (shortcut, action_opt) in &config.key_binds {
+        let Some(key_bind) = parse_key_bind(shortcut) else {
+            conflicts.push(fl!("shortcut-invalid", shortcut = shortcut.as_str()));
+            continue;
+        };
+        if !seen.insert(key_bind.clone()) {
+            conflicts.push(fl!("shortcut-duplicate", shortcut = key_bind.to_string()));
+        }
+        if let (Some(action), Some(default_action)) = (action_opt, defaults.get(&key_bind)) {
+            if action != default_action {
+                conflicts.push(fl!(
+                    "shortcut-replaces-default",
+                    shortcut = key_bind.to_string(),
+                    action = default_action.title()
+                ));
+            }
+        }
+    }
+    conflicts
+}
+
+// Shortcuts currently bound to an action, sorted for stable display
+pub fn shortcuts_for_action(key_binds: &HashMap<KeyBind, Action>, action: Action) -> Vec<String> {
+    let mut shortcuts: Vec<String> = key_binds
+        .iter()
+        .filter(|(_, key_action)| **key_action == action)
+        .map(|(key_bind, _)| key_bind.to_string())
+        .collect();
+    shortcuts.sort();
+    shortcuts
+}
+
+// Parse shortcuts like `Ctrl+Shift+T`, `Ctrl + Alt + d`, or `Shift+Insert`
+pub fn parse_key_bind(shortcut: &str) -> Option<KeyBind> {
+    let mut parts: Vec<&str> = shortcut.split('+').map(str::trim).collect();
+    let len = parts.len();
+    // A trailing `+` is the key itself, as in `Ctrl++`
+    let key_name = if len >= 2 && parts[len - 1].is_empty() && parts[len - 2].is_empty() {
+        parts.truncate(len - 2);
+        "+"
+    } else {
+        parts.pop()?
+    };
+    if key_name.is_empty() {
+        return None;
+    }
+
+    let mut modifiers = Vec::with_capacity(parts.len());
+    for part in parts {
+        let modifier = parse_modifier(part)?;
+        if !modifiers.contains(&modifier) {
+            modifiers.push(modifier);
+        }
+    }
+    // Keep the same modifier order as the default bindings so that lookups match
+    modifiers.sort_by_key(|modifier| match modifier {
+        Modifier::Super => 0,
+        Modifier::Ctrl => 1,
+        Modifier::Alt => 2,
+        Modifier::Shift => 3,
+    });
+
+    let key = match parse_named(key_name) {
+        Some(named) => Key::Named(named),
+        None => {
+            let mut chars = key_name.chars();
+            let c = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+            // Match how the keyboard reports letters: uppercase when Shift is held
+            if modifiers.contains(&Modifier::Shift) {
+                Key::Character(c.to_uppercase().to_string().into())
+            } else {
+                Key::Character(c.to_lowercase().to_string().into())
+            }
+        }
+    };
+
+    Some(KeyBind { modifiers, key })
+}
+
+fn parse_modifier(name: &str) -> Option<Modifier> {
+    match name.to_lowercase().as_str() {
+        "super" | "logo" | "meta" => Some(Modifier::Super),
+        "ctrl" | "control" => Some(Modifier::Ctrl),
+        "alt" => Some(Modifier::Alt),
+        "shift" => Some(Modifier::Shift),
+        _ => None,
+    }
+}
+
+fn parse_named(name: &str) -> Option<Named> {
+    let named = match name.to_lowercase().as_str() {
+        "tab" => Named::Tab,
+        "enter" | "return" => Named::Enter,
+        "escape" | "esc" => Named::Escape,
+        "space" => Named::Space,
+        "backspace" => Named::Backspace,
+        "insert" => Named::Insert,
+        "delete" | "del" => Named::Delete,
+        "home" => Named::Home,
+        "end" => Named::End,
+        "pageup" => Named::PageUp,
+        "pagedown" => Named::PageDown,
+        "arrowup" | "up" => Named::ArrowUp,
+        "arrowdown" | "down" => Named::ArrowDown,
+        "arrowleft" | "left" => Named::ArrowLeft,
+        "arrowright" | "right" => Named::ArrowRight,
+        "f1" => Named::F1,
+        "f2" => Named::F2,
+        "f3" => Named::F3,
+        "f4" => Named::F4,
+        "f5" => Named::F5,
+        "f6" => Named::F6,
+        "f7" => Named::F7,
+        "f8" => Named::F8,
+        "f9" => Named::F9,
+        "f10" => Named::F10,
+        "f11" => Named::F11,
+        "f12" => Named::F12,
+        _ => return None,
+    };
+    Some(named)
+}
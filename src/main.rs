@@ -8,7 +8,8 @@ use app::app::App;
 use app::flags::Flags;
 use config::color_scheme::{ColorSchemeId, ColorSchemeKind};
 use config::config::Config;
-use config::constants::CONFIG_VERSION;
+use config::constants::{CONFIG_VERSION, SESSION_STATE_VERSION};
+use config::session::SessionState;
 use cosmic::{
     app::Settings,
     cosmic_config::{self, CosmicConfigEntry},
@@ -27,6 +28,8 @@ mod key_bind;
 mod localization;
 mod menu;
 mod mouse_reporter;
+#[cfg(unix)]
+mod notify;
 mod terminal;
 mod terminal_box;
 mod terminal_theme;
@@ -102,6 +105,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    let (state_handler, state) =
+        match cosmic_config::Config::new_state(App::APP_ID, SESSION_STATE_VERSION) {
+            Ok(state_handler) => {
+                let state = match SessionState::get_entry(&state_handler) {
+                    Ok(ok) => ok,
+                    Err((errs, state)) => {
+                        log::info!("errors loading session state: {:?}", errs);
+                        state
+                    }
+                };
+                (Some(state_handler), state)
+            }
+            Err(err) => {
+                log::error!("failed to create session state handler: {}", err);
+                (None, SessionState::default())
+            }
+        };
+
     let startup_options = if let Some(shell_program) = shell_program_opt {
         let options = tty::Options {
             shell: Some(tty::Shell::new(shell_program, shell_args)),
@@ -112,7 +133,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         None
     };
 
-    let term_config = term::Config::default();
+    let term_config = term::Config {
+        // Programs can opt into the kitty keyboard protocol
+        kitty_keyboard: true,
+        ..term::Config::default()
+    };
     // Set up environmental variables for terminal
     tty::setup_env();
     // Override TERM for better compatibility
@@ -125,6 +150,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let flags = Flags {
         config_handler,
         config,
+        state_handler,
+        state,
         startup_options,
         term_config,
     };
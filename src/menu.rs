@@ -63,6 +63,7 @@ pub fn context_menu<'a>(
         menu_item(fl!("copy"), Action::Copy),
         menu_item(fl!("paste"), Action::Paste),
         menu_item(fl!("select-all"), Action::SelectAll),
+        menu_item(fl!("select-command-output"), Action::SelectCommandOutput),
         horizontal_rule(1),
         menu_item(fl!("split-horizontal"), Action::PaneSplitHorizontal),
         menu_item(fl!("split-vertical"), Action::PaneSplitVertical),
@@ -181,6 +182,12 @@ pub fn menu_bar<'a>(config: &Config, key_binds: &HashMap<KeyBind, Action>) -> El
                     MenuItem::Button(fl!("select-all"), Action::SelectAll),
                     MenuItem::Divider,
                     MenuItem::Button(fl!("find"), Action::Find),
+                    MenuItem::Divider,
+                    MenuItem::Button(fl!("previous-prompt"), Action::PromptPrevious),
+                    MenuItem::Button(fl!("next-prompt"), Action::PromptNext),
+                    MenuItem::Button(fl!("select-command-output"), Action::SelectCommandOutput),
+                    MenuItem::Divider,
+                    MenuItem::Button(fl!("vi-mode"), Action::ToggleViMode),
                 ],
             ),
         ),
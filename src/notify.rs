@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use zbus::{zvariant::Value, Connection};
+
+const NOTIFICATIONS_DESTINATION: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const NOTIFICATIONS_INTERFACE: &str = "org.freedesktop.Notifications";
+
+// Session bus connection shared by all notifications, opened on first use
+static SESSION: Mutex<Option<Connection>> = Mutex::const_new(None);
+
+/// Notification sent to the freedesktop notification service
+#[derive(Clone, Debug)]
+pub struct DesktopNotification {
+    pub app_name: String,
+    pub app_id: String,
+    pub summary: String,
+    pub body: String,
+}
+
+impl DesktopNotification {
+    /// Send the notification on the session bus, returning the id chosen by the server
+    pub async fn send(&self) -> zbus::Result<u32> {
+        let connection = {
+            let mut session = SESSION.lock().await;
+            match &*session {
+                Some(connection) => connection.clone(),
+                None => session.insert(Connection::session().await?).clone(),
+            }
+        };
+        let result = self.send_to(&connection, NOTIFICATIONS_DESTINATION).await;
+        // Reconnect next time in case the bus went away
+        if result.is_err() {
+            *SESSION.lock().await = None;
+        }
+        result
+    }
+
+    /// Send the notification to a specific bus name, which allows using another server
+    pub async fn send_to(&self, connection: &Connection, destination: &str) -> zbus::Result<u32> {
+        let mut hints = HashMap::new();
+        // Lets the server find the icon and group notifications by application
+        hints.insert("desktop-entry", Value::from(self.app_id.as_str()));
+
+        let reply = connection
+            .call_method(
+                Some(destination),
+                NOTIFICATIONS_PATH,
+                Some(NOTIFICATIONS_INTERFACE),
+                "Notify",
+                &(
+                    self.app_name.as_str(),
+                    0u32,
+                    self.app_id.as_str(),
+                    self.summary.as_str(),
+                    self.body.as_str(),
+                    Vec::<&str>::new(),
+                    hints,
+                    -1i32,
+                ),
+            )
+            .await?;
+        reply.body().deserialize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::UnixStream;
+    use zbus::{connection::Builder, zvariant::OwnedValue, Guid};
+
+    #[derive(Debug, PartialEq)]
+    struct Received {
+        app_name: String,
+        replaces_id: u32,
+        app_icon: String,
+        summary: String,
+        body: String,
+        actions: Vec<String>,
+        desktop_entry: Option<String>,
+        expire_timeout: i32,
+    }
+
+    struct MockServer {
+        received: Arc<Mutex<Vec<Received>>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl MockServer {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            app_name: String,
+            replaces_id: u32,
+            app_icon: String,
+            summary: String,
+            body: String,
+            actions: Vec<String>,
+            hints: HashMap<String, OwnedValue>,
+            expire_timeout: i32,
+        ) -> u32 {
+            let desktop_entry = hints.get("desktop-entry").and_then(|value| match &**value {
+                Value::Str(desktop_entry) => Some(desktop_entry.to_string()),
+                _ => None,
+            });
+            let mut received = self.received.lock().unwrap();
+            received.push(Received {
+                app_name,
+                replaces_id,
+                app_icon,
+                summary,
+                body,
+                actions,
+                desktop_entry,
+                expire_timeout,
+            });
+            received.len() as u32
+        }
+    }
+
+    #[tokio::test]
+    async fn send_to_mock_server() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let server = Builder::unix_stream(server_stream)
+            .server(Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at(
+                NOTIFICATIONS_PATH,
+                MockServer {
+                    received: received.clone(),
+                },
+            )
+            .unwrap()
+            .build();
+        let client = Builder::unix_stream(client_stream).p2p().build();
+        let (_server, client) = tokio::try_join!(server, client).unwrap();
+
+        let notification = DesktopNotification {
+            app_name: "COSMIC Terminal".to_string(),
+            app_id: "com.system76.CosmicTerm".to_string(),
+            summary: "Build".to_string(),
+            body: "Command finished; took 12 seconds".to_string(),
+        };
+        let id = notification
+            .send_to(&client, NOTIFICATIONS_DESTINATION)
+            .await
+            .unwrap();
+
+        assert_eq!(id, 1);
+        assert_eq!(
+            *received.lock().unwrap(),
+            [Received {
+                app_name: "COSMIC Terminal".to_string(),
+                replaces_id: 0,
+                app_icon: "com.system76.CosmicTerm".to_string(),
+                summary: "Build".to_string(),
+                body: "Command finished; took 12 seconds".to_string(),
+                actions: Vec::new(),
+                desktop_entry: Some("com.system76.CosmicTerm".to_string()),
+                expire_timeout: -1,
+            }]
+        );
+    }
+}
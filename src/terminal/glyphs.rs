@@ -0,0 +1,625 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+// Box drawing, block element, braille and Powerline glyphs, drawn procedurally so they fill
+// their cells exactly and connect across cells whatever the font and line height
+
+// Line weights of box drawing arms
+const N: u8 = 0;
+const L: u8 = 1;
+const H: u8 = 2;
+const D: u8 = 3;
+
+// Arms of U+2500 to U+257F, as up, right, down and left. Dashes, arcs and diagonals use the
+// weights here only for their direction and thickness, or are drawn separately
+#[rustfmt::skip]
+const LINES: [[u8; 4]; 128] = [
+    [N, L, N, L], // ─
+    [N, H, N, H], // ━
+    [L, N, L, N], // │
+    [H, N, H, N], // ┃
+    [N, L, N, L], // ┄
+    [N, H, N, H], // ┅
+    [L, N, L, N], // ┆
+    [H, N, H, N], // ┇
+    [N, L, N, L], // ┈
+    [N, H, N, H], // ┉
+    [L, N, L, N], // ┊
+    [H, N, H, N], // ┋
+    [N, L, L, N], // ┌
+    [N, H, L, N], // ┍
+    [N, L, H, N], // ┎
+    [N, H, H, N], // ┏
+    [N, N, L, L], // ┐
+    [N, N, L, H], // ┑
+    [N, N, H, L], // ┒
+    [N, N, H, H], // ┓
+    [L, L, N, N], // └
+    [L, H, N, N], // ┕
+    [H, L, N, N], // ┖
+    [H, H, N, N], // ┗
+    [L, N, N, L], // ┘
+    [L, N, N, H], // ┙
+    [H, N, N, L], // ┚
+    [H, N, N, H], // ┛
+    [L, L, L, N], // ├
+    [L, H, L, N], // ┝
+    [H, L, L, N], // ┞
+    [L, L, H, N], // ┟
+    [H, L, H, N], // ┠
+    [H, H, L, N], // ┡
+    [L, H, H, N], // ┢
+    [H, H, H, N], // ┣
+    [L, N, L, L], // ┤
+    [L, N, L, H], // ┥
+    [H, N, L, L], // ┦
+    [L, N, H, L], // ┧
+    [H, N, H, L], // ┨
+    [H, N, L, H], // ┩
+    [L, N, H, H], // ┪
+    [H, N, H, H], // ┫
+    [N, L, L, L], // ┬
+    [N, L, L, H], // ┭
+    [N, H, L, L], // ┮
+    [N, H, L, H], // ┯
+    [N, L, H, L], // ┰
+    [N, L, H, H], // ┱
+    [N, H, H, L], // ┲
+    [N, H, H, H], // ┳
+    [L, L, N, L], // ┴
+    [L, L, N, H], // ┵
+    [L, H, N, L], // ┶
+    [L, H, N, H], // ┷
+    [H, L, N, L], // ┸
+    [H, L, N, H], // ┹
+    [H, H, N, L], // ┺
+    [H, H, N, H], // ┻
+    [L, L, L, L], // ┼
+    [L, L, L, H], // ┽
+    [L, H, L, L], // ┾
+    [L, H, L, H], // ┿
+    [H, L, L, L], // ╀
+    [L, L, H, L], // ╁
+    [H, L, H, L], // ╂
+    [H, L, L, H], // ╃
+    [H, H, L, L], // ╄
+    [L, L, H, H], // ╅
+    [L, H, H, L], // ╆
+    [H, H, L, H], // ╇
+    [L, H, H, H], // ╈
+    [H, L, H, H], // ╉
+    [H, H, H, L], // ╊
+    [H, H, H, H], // ╋
+    [N, L, N, L], // ╌
+    [N, H, N, H], // ╍
+    [L, N, L, N], // ╎
+    [H, N, H, N], // ╏
+    [N, D, N, D], // ═
+    [D, N, D, N], // ║
+    [N, D, L, N], // ╒
+    [N, L, D, N], // ╓
+    [N, D, D, N], // ╔
+    [N, N, L, D], // ╕
+    [N, N, D, L], // ╖
+    [N, N, D, D], // ╗
+    [L, D, N, N], // ╘
+    [D, L, N, N], // ╙
+    [D, D, N, N], // ╚
+    [L, N, N, D], // ╛
+    [D, N, N, L], // ╜
+    [D, N, N, D], // ╝
+    [L, D, L, N], // ╞
+    [D, L, D, N], // ╟
+    [D, D, D, N], // ╠
+    [L, N, L, D], // ╡
+    [D, N, D, L], // ╢
+    [D, N, D, D], // ╣
+    [N, D, L, D], // ╤
+    [N, L, D, L], // ╥
+    [N, D, D, D], // ╦
+    [L, D, N, D], // ╧
+    [D, L, N, L], // ╨
+    [D, D, N, D], // ╩
+    [L, D, L, D], // ╪
+    [D, L, D, L], // ╫
+    [D, D, D, D], // ╬
+    [N, N, N, N], // ╭
+    [N, N, N, N], // ╮
+    [N, N, N, N], // ╯
+    [N, N, N, N], // ╰
+    [N, N, N, N], // ╱
+    [N, N, N, N], // ╲
+    [N, N, N, N], // ╳
+    [N, N, N, L], // ╴
+    [L, N, N, N], // ╵
+    [N, L, N, N], // ╶
+    [N, N, L, N], // ╷
+    [N, N, N, H], // ╸
+    [H, N, N, N], // ╹
+    [N, H, N, N], // ╺
+    [N, N, H, N], // ╻
+    [N, H, N, L], // ╼
+    [L, N, H, N], // ╽
+    [N, L, N, H], // ╾
+    [H, N, L, N], // ╿
+];
+
+// Samples per pixel in each direction for antialiased shapes
+const SAMPLES: usize = 4;
+
+/// Whether a character is drawn by [`rasterize`] instead of the font
+pub fn is_builtin(c: char) -> bool {
+    matches!(
+        c,
+        '\u{2500}'..='\u{259F}' | '\u{2800}'..='\u{28FF}' | '\u{E0B0}'..='\u{E0BF}'
+    )
+}
+
+/// Rectangle of a builtin character, in pixels from the top left of the cell
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub alpha: u8,
+}
+
+/// Draw a builtin character into an alpha mask of the given size, returns `None` if it is not
+/// builtin
+pub fn rasterize(c: char, width: usize, height: usize) -> Option<Vec<u8>> {
+    if !is_builtin(c) {
+        return None;
+    }
+
+    let mut canvas = Canvas {
+        width,
+        height,
+        alpha: vec![0; width * height],
+        recording: false,
+        rects_opt: None,
+    };
+    canvas.draw(c);
+    Some(canvas.alpha)
+}
+
+/// Split a builtin character into the rectangles that [`rasterize`] would fill, returns `None`
+/// if it is not builtin or has antialiased or cut out parts
+pub fn rectangles(c: char, width: usize, height: usize) -> Option<Vec<Rect>> {
+    if !is_builtin(c) {
+        return None;
+    }
+
+    let mut canvas = Canvas {
+        width,
+        height,
+        alpha: Vec::new(),
+        recording: true,
+        rects_opt: Some(Vec::new()),
+    };
+    canvas.draw(c);
+    canvas.rects_opt
+}
+
+// Start and end of a band of the given thickness centered in size
+fn band(size: usize, thickness: usize) -> (usize, usize) {
+    let start = size.saturating_sub(thickness) / 2;
+    (start, start + thickness)
+}
+
+// Position of n eighths of size, rounded so that complementary parts tile exactly
+fn eighths(size: usize, n: usize) -> usize {
+    (size * n + 4) / 8
+}
+
+fn segment_distance(x: f32, y: f32, from: (f32, f32), to: (f32, f32)) -> f32 {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let len_sq = dx * dx + dy * dy;
+    let t = if len_sq > 0.0 {
+        (((x - from.0) * dx + (y - from.1) * dy) / len_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (px, py) = (from.0 + t * dx, from.1 + t * dy);
+    ((x - px) * (x - px) + (y - py) * (y - py)).sqrt()
+}
+
+struct Canvas {
+    width: usize,
+    height: usize,
+    alpha: Vec<u8>,
+    // Rectangles are recorded in rects_opt instead of drawn, which is reset if anything else
+    // is drawn
+    recording: bool,
+    rects_opt: Option<Vec<Rect>>,
+}
+
+impl Canvas {
+    fn draw(&mut self, c: char) {
+        // Thin lines are one pixel until cells get large
+        let light = (self.width as f32 / 8.0).round().max(1.0) as usize;
+        match c {
+            '\u{2500}'..='\u{257F}' => self.box_drawing(c, light),
+            '\u{2580}'..='\u{259F}' => self.block(c),
+            '\u{2800}'..='\u{28FF}' => self.braille(c),
+            _ => self.powerline(c, light),
+        }
+    }
+
+    // Fill a rectangle, clamped to the canvas
+    fn rect(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, alpha: u8) {
+        if self.recording {
+            let (x1, y1) = (x1.min(self.width), y1.min(self.height));
+            if let Some(rects) = &mut self.rects_opt {
+                if x0 < x1 && y0 < y1 {
+                    rects.push(Rect {
+                        x: x0,
+                        y: y0,
+                        width: x1 - x0,
+                        height: y1 - y0,
+                        alpha,
+                    });
+                }
+            }
+            return;
+        }
+        for y in y0..y1.min(self.height) {
+            for x in x0..x1.min(self.width) {
+                let pixel = &mut self.alpha[y * self.width + x];
+                *pixel = (*pixel).max(alpha);
+            }
+        }
+    }
+
+    fn clear(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        if self.recording {
+            self.rects_opt = None;
+            return;
+        }
+        for y in y0..y1.min(self.height) {
+            for x in x0..x1.min(self.width) {
+                self.alpha[y * self.width + x] = 0;
+            }
+        }
+    }
+
+    // Fill where `inside` is true for the sample positions, in pixels
+    fn shape(&mut self, inside: impl Fn(f32, f32) -> bool) {
+        if self.recording {
+            self.rects_opt = None;
+            return;
+        }
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut count = 0;
+                for sy in 0..SAMPLES {
+                    for sx in 0..SAMPLES {
+                        let px = x as f32 + (sx as f32 + 0.5) / SAMPLES as f32;
+                        let py = y as f32 + (sy as f32 + 0.5) / SAMPLES as f32;
+                        if inside(px, py) {
+                            count += 1;
+                        }
+                    }
+                }
+                let pixel = &mut self.alpha[y * self.width + x];
+                *pixel = (*pixel).max((count * 255 / (SAMPLES * SAMPLES)) as u8);
+            }
+        }
+    }
+
+    fn line(&mut self, from: (f32, f32), to: (f32, f32), thickness: usize) {
+        let half = thickness as f32 / 2.0;
+        self.shape(|x, y| segment_distance(x, y, from, to) <= half);
+    }
+
+    fn box_drawing(&mut self, c: char, light: usize) {
+        let arms = LINES[c as usize - 0x2500];
+        let thickness = |weight: u8| match weight {
+            L => light,
+            H => light * 2,
+            D => light * 3,
+            _ => 0,
+        };
+        match c {
+            '\u{2504}'..='\u{250B}' | '\u{254C}'..='\u{254F}' => {
+                let dashes = match c {
+                    '\u{2504}'..='\u{2507}' => 3,
+                    '\u{2508}'..='\u{250B}' => 4,
+                    _ => 2,
+                };
+                let vertical = arms[0] != N;
+                let thickness = thickness(arms[0].max(arms[1]));
+                let length = if vertical { self.height } else { self.width };
+                for i in 0..dashes {
+                    let start = length * i / dashes;
+                    let end = length * (i + 1) / dashes;
+                    let gap = (end - start) / 4;
+                    if vertical {
+                        let (x0, x1) = band(self.width, thickness);
+                        self.rect(x0, start + gap, x1, end - gap, 255);
+                    } else {
+                        let (y0, y1) = band(self.height, thickness);
+                        self.rect(start + gap, y0, end - gap, y1, 255);
+                    }
+                }
+            }
+            '\u{256D}'..='\u{2570}' => {
+                let (sx, sy) = match c {
+                    '\u{256D}' => (1.0, 1.0),
+                    '\u{256E}' => (-1.0, 1.0),
+                    '\u{256F}' => (-1.0, -1.0),
+                    _ => (1.0, -1.0),
+                };
+                let (x0, _) = band(self.width, light);
+                let (y0, _) = band(self.height, light);
+                let half = light as f32 / 2.0;
+                let cx = x0 as f32 + half;
+                let cy = y0 as f32 + half;
+                let radius = (self.width as f32 / 2.0).min(self.height as f32 / 2.0);
+                self.shape(|x, y| {
+                    let dx = (x - cx) * sx;
+                    let dy = (y - cy) * sy;
+                    if dy >= radius {
+                        // Straight vertical part
+                        (x - cx).abs() <= half
+                    } else if dx >= radius {
+                        // Straight horizontal part
+                        (y - cy).abs() <= half
+                    } else {
+                        let distance = ((radius - dx).powi(2) + (radius - dy).powi(2)).sqrt();
+                        dx >= -half && dy >= -half && (distance - radius).abs() <= half
+                    }
+                });
+            }
+            '\u{2571}'..='\u{2573}' => {
+                let (w, h) = (self.width as f32, self.height as f32);
+                if c != '\u{2572}' {
+                    self.line((w, 0.0), (0.0, h), light);
+                }
+                if c != '\u{2571}' {
+                    self.line((0.0, 0.0), (w, h), light);
+                }
+            }
+            _ => {
+                let [up, right, down, left] = arms;
+                let vertical = thickness(up).max(thickness(down));
+                let horizontal = thickness(left).max(thickness(right));
+                // Arms cross the center far enough to join the perpendicular ones
+                let join = |perpendicular: usize, own: usize| {
+                    if perpendicular > 0 {
+                        perpendicular
+                    } else {
+                        own
+                    }
+                };
+                let draw_arms = |canvas: &mut Self, double: bool| {
+                    for (i, &weight) in arms.iter().enumerate() {
+                        if weight == N || (weight == D) != double {
+                            continue;
+                        }
+                        let own = thickness(weight);
+                        match i {
+                            0 | 2 => {
+                                let (x0, x1) = band(canvas.width, own);
+                                let (y0, y1) = band(canvas.height, join(horizontal, own));
+                                if i == 0 {
+                                    canvas.rect(x0, 0, x1, y1, 255);
+                                } else {
+                                    canvas.rect(x0, y0, x1, canvas.height, 255);
+                                }
+                            }
+                            _ => {
+                                let (y0, y1) = band(canvas.height, own);
+                                let (x0, x1) = band(canvas.width, join(vertical, own));
+                                if i == 3 {
+                                    canvas.rect(0, y0, x1, y1, 255);
+                                } else {
+                                    canvas.rect(x0, y0, canvas.width, y1, 255);
+                                }
+                            }
+                        }
+                    }
+                };
+
+                // Double lines are drawn as thick lines with the middle removed, before the
+                // other lines so that those cross them
+                draw_arms(self, true);
+                let (gx0, gx1) = band(self.width, light);
+                let (gy0, gy1) = band(self.height, light);
+                if up == D {
+                    self.clear(gx0, 0, gx1, gy1);
+                }
+                if right == D {
+                    self.clear(gx0, gy0, self.width, gy1);
+                }
+                if down == D {
+                    self.clear(gx0, gy0, gx1, self.height);
+                }
+                if left == D {
+                    self.clear(0, gy0, gx1, gy1);
+                }
+                draw_arms(self, false);
+            }
+        }
+    }
+
+    fn block(&mut self, c: char) {
+        let (w, h) = (self.width, self.height);
+        let (mx, my) = (eighths(w, 4), eighths(h, 4));
+        let quadrants = |canvas: &mut Self, filled: [bool; 4]| {
+            let [upper_left, upper_right, lower_left, lower_right] = filled;
+            if upper_left {
+                canvas.rect(0, 0, mx, my, 255);
+            }
+            if upper_right {
+                canvas.rect(mx, 0, w, my, 255);
+            }
+            if lower_left {
+                canvas.rect(0, my, mx, h, 255);
+            }
+            if lower_right {
+                canvas.rect(mx, my, w, h, 255);
+            }
+        };
+        match c {
+            '\u{2580}' => self.rect(0, 0, w, my, 255),
+            '\u{2581}'..='\u{2588}' => {
+                let n = c as usize - 0x2580;
+                self.rect(0, eighths(h, 8 - n), w, h, 255);
+            }
+            '\u{2589}'..='\u{258F}' => {
+                let n = 0x2590 - c as usize;
+                self.rect(0, 0, eighths(w, n), h, 255);
+            }
+            '\u{2590}' => self.rect(mx, 0, w, h, 255),
+            '\u{2591}' => self.rect(0, 0, w, h, 64),
+            '\u{2592}' => self.rect(0, 0, w, h, 128),
+            '\u{2593}' => self.rect(0, 0, w, h, 191),
+            '\u{2594}' => self.rect(0, 0, w, eighths(h, 1), 255),
+            '\u{2595}' => self.rect(eighths(w, 7), 0, w, h, 255),
+            '\u{2596}' => quadrants(self, [false, false, true, false]),
+            '\u{2597}' => quadrants(self, [false, false, false, true]),
+            '\u{2598}' => quadrants(self, [true, false, false, false]),
+            '\u{2599}' => quadrants(self, [true, false, true, true]),
+            '\u{259A}' => quadrants(self, [true, false, false, true]),
+            '\u{259B}' => quadrants(self, [true, true, true, false]),
+            '\u{259C}' => quadrants(self, [true, true, false, true]),
+            '\u{259D}' => quadrants(self, [false, true, false, false]),
+            '\u{259E}' => quadrants(self, [false, true, true, false]),
+            _ => quadrants(self, [false, true, true, true]),
+        }
+    }
+
+    fn braille(&mut self, c: char) {
+        // Column and row of the dot for each bit
+        const DOTS: [(usize, usize); 8] = [
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 0),
+            (1, 1),
+            (1, 2),
+            (0, 3),
+            (1, 3),
+        ];
+        let bits = c as usize - 0x2800;
+        for (bit, &(column, row)) in DOTS.iter().enumerate() {
+            if bits & (1 << bit) == 0 {
+                continue;
+            }
+            let x0 = self.width * column / 2;
+            let x1 = self.width * (column + 1) / 2;
+            let y0 = self.height * row / 4;
+            let y1 = self.height * (row + 1) / 4;
+            let size = ((x1 - x0).min(y1 - y0) / 2).max(1);
+            let x = x0 + (x1 - x0 - size.min(x1 - x0)) / 2;
+            let y = y0 + (y1 - y0 - size.min(y1 - y0)) / 2;
+            self.rect(x, y, x + size, y + size, 255);
+        }
+    }
+
+    fn powerline(&mut self, c: char, light: usize) {
+        let (w, h) = (self.width as f32, self.height as f32);
+        // Distance from the vertical middle, from 0 to 1
+        let middle = move |y: f32| (2.0 * y / h - 1.0).abs();
+        // Half ellipse touching the left or right edge, scaled down by inset pixels
+        let ellipse = move |x: f32, y: f32, right: bool, inset: f32| {
+            let dx = if right { x } else { w - x };
+            let dx = dx / (w - inset).max(1.0);
+            let dy = (y - h / 2.0) / (h / 2.0 - inset).max(1.0);
+            dx * dx + dy * dy <= 1.0
+        };
+        let inset = light as f32;
+        match c {
+            '\u{E0B0}' => self.shape(|x, y| x / w <= 1.0 - middle(y)),
+            '\u{E0B1}' => {
+                self.line((0.0, 0.0), (w, h / 2.0), light);
+                self.line((w, h / 2.0), (0.0, h), light);
+            }
+            '\u{E0B2}' => self.shape(|x, y| x / w >= middle(y)),
+            '\u{E0B3}' => {
+                self.line((w, 0.0), (0.0, h / 2.0), light);
+                self.line((0.0, h / 2.0), (w, h), light);
+            }
+            '\u{E0B4}' => self.shape(|x, y| ellipse(x, y, true, 0.0)),
+            '\u{E0B5}' => {
+                self.shape(|x, y| ellipse(x, y, true, 0.0) && !ellipse(x, y, true, inset))
+            }
+            '\u{E0B6}' => self.shape(|x, y| ellipse(x, y, false, 0.0)),
+            '\u{E0B7}' => {
+                self.shape(|x, y| ellipse(x, y, false, 0.0) && !ellipse(x, y, false, inset))
+            }
+            '\u{E0B8}' => self.shape(|x, y| y / h >= x / w),
+            '\u{E0BA}' => self.shape(|x, y| y / h >= 1.0 - x / w),
+            '\u{E0BC}' => self.shape(|x, y| y / h <= 1.0 - x / w),
+            '\u{E0BE}' => self.shape(|x, y| y / h <= x / w),
+            '\u{E0B9}' | '\u{E0BF}' => self.line((0.0, 0.0), (w, h), light),
+            _ => self.line((w, 0.0), (0.0, h), light),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Number of rectangles covering each pixel of the cell
+    fn coverage(chars: &[char], width: usize, height: usize) -> Vec<usize> {
+        let mut counts = vec![0; width * height];
+        for &c in chars {
+            for rect in rectangles(c, width, height).unwrap() {
+                assert_eq!(rect.alpha, 255);
+                for y in rect.y..rect.y + rect.height {
+                    for x in rect.x..rect.x + rect.width {
+                        counts[y * width + x] += 1;
+                    }
+                }
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn full_block() {
+        for (width, height) in [(1, 1), (7, 15), (8, 16), (11, 23)] {
+            assert_eq!(
+                rectangles('█', width, height),
+                Some(vec![Rect {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                    alpha: 255,
+                }])
+            );
+        }
+    }
+
+    #[test]
+    fn complementary_blocks() {
+        let pairs = [
+            ('▀', '▄'),
+            ('▔', '▇'),
+            ('▌', '▐'),
+            ('▉', '▕'),
+            ('▘', '▟'),
+            ('▝', '▙'),
+            ('▖', '▜'),
+            ('▗', '▛'),
+            ('▚', '▞'),
+        ];
+        for (width, height) in [(7, 15), (8, 16), (9, 17), (11, 23)] {
+            for (a, b) in pairs {
+                assert!(
+                    coverage(&[a, b], width, height).iter().all(|&n| n == 1),
+                    "{a} and {b} at {width}x{height}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn not_builtin() {
+        assert_eq!(rectangles('a', 8, 16), None);
+        assert_eq!(rasterize('a', 8, 16), None);
+    }
+}
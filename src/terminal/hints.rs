@@ -0,0 +1,195 @@
+use alacritty_terminal::{
+    index::{Boundary, Column, Direction, Point},
+    term::search::{Match, RegexIter, RegexSearch},
+    Term,
+};
+use std::path::Path;
+
+// Schemes are kept in sync with the ones alacritty highlights by default
+const URL_REGEX: &str = "(?:https?://|file:|ftp://|git://|ssh:|mailto:|magnet:|gemini://|gopher://|news:)[^\u{0000}-\u{001F}\u{007F}-\u{009F}<>\"\\s{}\\^⟨⟩`]+";
+const EMAIL_REGEX: &str = r"[\w.+-]+@[\w-]+(?:\.[\w-]+)+";
+// Absolute and relative paths. Absolute ones must not follow a word, like in `and/or` or dates,
+// and relative ones need a line number and a directory or file extension, so that times and
+// ports are not paths
+const PATH_REGEX: &str = concat!(
+    r"(?-u:\B)(?:~|\.{1,2})?/[\w.@+-]+(?:/[\w.@+-]+)*(?::\d+(?::\d+)?)?",
+    r"|(?-u:\b)\w[\w.@+-]*(?:/[\w.@+-]+)+:\d+(?::\d+)?",
+    r"|(?-u:\b)\w[\w@+-]*(?:\.[\w@+-]+)*\.[A-Za-z]\w*:\d+(?::\d+)?",
+);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HintKind {
+    Url,
+    Email,
+    Path,
+    Hyperlink,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Hint {
+    pub kind: HintKind,
+    pub range: Match,
+    pub text: String,
+}
+
+impl Hint {
+    /// Convert the hint into something that can be passed to `open::that_detached`
+    pub fn url(&self, cwd_opt: Option<&Path>) -> String {
+        match self.kind {
+            HintKind::Url | HintKind::Hyperlink => self.text.clone(),
+            HintKind::Email => format!("mailto:{}", self.text),
+            HintKind::Path => {
+                // Strip line and column numbers, openers do not understand them
+                let mut path = self.text.as_str();
+                for _ in 0..2 {
+                    if let Some((prefix, suffix)) = path.rsplit_once(':') {
+                        if !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit()) {
+                            path = prefix;
+                        }
+                    }
+                }
+
+                if let Some(relative) = path.strip_prefix("~/") {
+                    if let Some(home) = std::env::var_os("HOME") {
+                        return Path::new(&home).join(relative).display().to_string();
+                    }
+                }
+                match cwd_opt {
+                    Some(cwd) if !path.starts_with('/') => cwd.join(path).display().to_string(),
+                    _ => path.to_string(),
+                }
+            }
+        }
+    }
+}
+
+pub struct HintRegexes {
+    regexes: Vec<(HintKind, RegexSearch)>,
+}
+
+impl HintRegexes {
+    pub fn new() -> Self {
+        let mut regexes = Vec::with_capacity(3);
+        // Order sets the priority when matches overlap
+        for (kind, pattern) in [
+            (HintKind::Url, URL_REGEX),
+            (HintKind::Email, EMAIL_REGEX),
+            (HintKind::Path, PATH_REGEX),
+        ] {
+            match RegexSearch::new(pattern) {
+                Ok(regex) => regexes.push((kind, regex)),
+                Err(err) => log::error!("failed to parse {:?} hint regex: {}", kind, err),
+            }
+        }
+        Self { regexes }
+    }
+
+    /// Find the hint under the given point, searching the whole (possibly wrapped) line
+    pub fn hint_at<T>(&mut self, term: &Term<T>, point: Point) -> Option<Hint> {
+        let start = term.line_search_left(Point::new(point.line, Column(0)));
+        let end = term.line_search_right(Point::new(point.line, Column(0)));
+        for (kind, regex) in self.regexes.iter_mut() {
+            let range_opt = RegexIter::new(start, end, Direction::Right, term, regex)
+                .take_while(|range| *range.start() <= point)
+                .find(|range| range.contains(&point));
+            if let Some(range) = range_opt {
+                let (start, mut end) = range.into_inner();
+                let mut text = term.bounds_to_string(start, end);
+                // Trailing punctuation is usually part of the sentence, not the link
+                while text.ends_with(['.', ',', ':', ';', '!', '?', '\'', ')']) {
+                    if text.ends_with(')') && text.matches('(').count() >= text.matches(')').count()
+                    {
+                        break;
+                    }
+                    text.pop();
+                    end = end.sub(term, Boundary::Grid, 1);
+                }
+                return Some(Hint {
+                    kind: *kind,
+                    range: start..=end,
+                    text,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Find the OSC 8 hyperlink under the given point, spanning all adjacent cells with the same link
+pub fn hyperlink_at<T>(term: &Term<T>, point: Point) -> Option<Hint> {
+    let grid = term.grid();
+    let hyperlink = grid[point].hyperlink()?;
+
+    let mut start = point;
+    loop {
+        let prev = start.sub(term, Boundary::Grid, 1);
+        if prev == start || grid[prev].hyperlink().as_ref() != Some(&hyperlink) {
+            break;
+        }
+        start = prev;
+    }
+
+    let mut end = point;
+    loop {
+        let next = end.add(term, Boundary::Grid, 1);
+        if next == end || grid[next].hyperlink().as_ref() != Some(&hyperlink) {
+            break;
+        }
+        end = next;
+    }
+
+    Some(Hint {
+        kind: HintKind::Hyperlink,
+        range: start..=end,
+        text: hyperlink.uri().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::{index::Line, term::test::mock_term};
+
+    // Text of the hint found at the column of `text`
+    fn hint(text: &str, column: usize) -> Option<String> {
+        let term = mock_term(text);
+        HintRegexes::new()
+            .hint_at(&term, Point::new(Line(0), Column(column)))
+            .map(|hint| hint.text)
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(hint("ls /usr/bin", 5).as_deref(), Some("/usr/bin"));
+        assert_eq!(hint("vi ~/notes.txt", 5).as_deref(), Some("~/notes.txt"));
+        assert_eq!(hint("cat ./a/b.", 6).as_deref(), Some("./a/b"));
+        assert_eq!(
+            hint("src/main.rs:12:5: error", 2).as_deref(),
+            Some("src/main.rs:12:5")
+        );
+        assert_eq!(hint("at main.rs:42", 5).as_deref(), Some("main.rs:42"));
+    }
+
+    #[test]
+    fn not_paths() {
+        assert_eq!(hint("and/or", 3), None);
+        assert_eq!(hint("1/2", 1), None);
+        assert_eq!(hint("2024/10/18", 5), None);
+        assert_eq!(hint("at 12:30", 5), None);
+        assert_eq!(hint("10:42:01", 3), None);
+        assert_eq!(hint("host:8080", 4), None);
+        assert_eq!(hint("user@host:22", 9), None);
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(
+            hint("see https://example.com/a.", 8).as_deref(),
+            Some("https://example.com/a")
+        );
+        assert_eq!(
+            hint("mail me@example.com", 8).as_deref(),
+            Some("me@example.com")
+        );
+    }
+}
@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+// iTerm2 inline images, sent as `OSC 1337 ; File=[arguments]:[base64 data] ST`.
+// See https://iterm2.com/documentation-images.html
+
+use base64::Engine;
+
+use super::{
+    sixel,
+    tap::{RgbaImage, BASE64},
+};
+
+// Largest file accepted, before decoding
+const MAX_FILE: usize = 64 * 1024 * 1024;
+
+/// Requested width or height of an image
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ImageSize {
+    /// Size of the image itself
+    #[default]
+    Auto,
+    Cells(f32),
+    Pixels(f32),
+    /// Percent of the terminal width or height
+    Percent(f32),
+}
+
+impl ImageSize {
+    fn parse(value: &str) -> Self {
+        let parse = |value: &str| value.parse::<f32>().ok().filter(|value| *value > 0.0);
+        if let Some(pixels) = value.strip_suffix("px") {
+            parse(pixels).map_or(Self::Auto, Self::Pixels)
+        } else if let Some(percent) = value.strip_suffix('%') {
+            parse(percent).map_or(Self::Auto, Self::Percent)
+        } else {
+            parse(value).map_or(Self::Auto, Self::Cells)
+        }
+    }
+
+    // Size in pixels, given the size of a cell and of the terminal
+    fn pixels(self, cell: f32, total: f32) -> Option<f32> {
+        match self {
+            Self::Auto => None,
+            Self::Cells(cells) => Some(cells * cell),
+            Self::Pixels(pixels) => Some(pixels),
+            Self::Percent(percent) => Some(percent * total / 100.0),
+        }
+    }
+}
+
+/// Decoded inline image with its requested size
+#[derive(Debug)]
+pub struct InlineImage {
+    pub image: RgbaImage,
+    pub width: ImageSize,
+    pub height: ImageSize,
+    pub preserve_aspect_ratio: bool,
+}
+
+impl InlineImage {
+    /// Parse the parameters of OSC 1337 after `File=`, returns `None` for files that are not
+    /// meant to be displayed or that could not be decoded
+    pub fn parse(file: &[u8]) -> Option<Self> {
+        let colon = file.iter().position(|&byte| byte == b':')?;
+        let (arguments, data) = (&file[..colon], &file[colon + 1..]);
+        if data.len() > MAX_FILE * 4 / 3 + 4 {
+            log::warn!("inline image too large");
+            return None;
+        }
+
+        let mut inline = false;
+        let mut width = ImageSize::Auto;
+        let mut height = ImageSize::Auto;
+        let mut preserve_aspect_ratio = true;
+        for argument in String::from_utf8_lossy(arguments).split(';') {
+            let Some((key, value)) = argument.split_once('=') else {
+                continue;
+            };
+            match key {
+                "inline" => inline = value == "1",
+                "width" => width = ImageSize::parse(value),
+                "height" => height = ImageSize::parse(value),
+                "preserveAspectRatio" => preserve_aspect_ratio = value != "0",
+                _ => {}
+            }
+        }
+        // Files that are not inline would be downloaded by iTerm2
+        if !inline {
+            return None;
+        }
+
+        // Some tools wrap the base64 data in lines
+        let data: Vec<u8> = data
+            .iter()
+            .copied()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .collect();
+        let data = match BASE64.decode(data) {
+            Ok(data) => data,
+            Err(err) => {
+                log::warn!("invalid base64 in inline image: {}", err);
+                return None;
+            }
+        };
+        let image = match RgbaImage::decode(&data, None) {
+            Ok(image) => image,
+            Err(err) => {
+                log::warn!("failed to decode inline image: {}", err);
+                return None;
+            }
+        };
+
+        Some(Self {
+            image,
+            width,
+            height,
+            preserve_aspect_ratio,
+        })
+    }
+
+    /// Size to draw the image at in pixels, given the size of a cell and of the terminal
+    pub fn display_size(
+        &self,
+        cell_width: f32,
+        cell_height: f32,
+        columns: usize,
+        lines: usize,
+    ) -> (f32, f32) {
+        let image_width = self.image.width as f32;
+        let image_height = self.image.height as f32;
+        let width_opt = self.width.pixels(cell_width, columns as f32 * cell_width);
+        let height_opt = self.height.pixels(cell_height, lines as f32 * cell_height);
+
+        match (width_opt, height_opt) {
+            // Shrink to the width of the terminal, and to the height sixels are cropped to
+            (None, None) => {
+                let scale = (columns as f32 * cell_width / image_width)
+                    .min(sixel::MAX_SIZE as f32 / image_height)
+                    .min(1.0);
+                (image_width * scale, image_height * scale)
+            }
+            (Some(width), None) if self.preserve_aspect_ratio => {
+                (width, width * image_height / image_width)
+            }
+            (None, Some(height)) if self.preserve_aspect_ratio => {
+                (height * image_width / image_height, height)
+            }
+            // Fit inside the requested box
+            (Some(width), Some(height)) if self.preserve_aspect_ratio => {
+                let scale = (width / image_width).min(height / image_height);
+                (image_width * scale, image_height * scale)
+            }
+            (width_opt, height_opt) => (
+                width_opt.unwrap_or(image_width),
+                height_opt.unwrap_or(image_height),
+            ),
+        }
+    }
+}
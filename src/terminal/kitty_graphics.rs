@@ -0,0 +1,564 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+// Kitty graphics protocol, sent in APC sequences starting with `G`.
+// See https://sw.kovidgoyal.net/kitty/graphics-protocol/
+
+use base64::Engine;
+use flate2::read::ZlibDecoder;
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    sync::Arc,
+};
+
+use super::tap::{RgbaImage, BASE64};
+
+// Largest payload accepted for one image, before decoding
+const MAX_PAYLOAD: usize = 64 * 1024 * 1024;
+// Images larger than this in either direction are rejected
+const MAX_SIZE: u32 = 10000;
+// Stored image data is evicted, oldest first, above this many bytes, like kitty does
+const STORAGE_QUOTA: usize = 320 * 1024 * 1024;
+
+#[derive(Clone, Debug, Default)]
+struct Command {
+    action: u8,
+    quiet: u32,
+    format: u32,
+    medium: u8,
+    compression: u8,
+    width: u32,
+    height: u32,
+    size: usize,
+    offset: u64,
+    id: u32,
+    number: u32,
+    placement_id: u32,
+    more: bool,
+    columns: u32,
+    rows: u32,
+    source_x: u32,
+    source_y: u32,
+    source_width: u32,
+    source_height: u32,
+    offset_x: u32,
+    offset_y: u32,
+    z_index: i32,
+    delete: u8,
+    payload: Vec<u8>,
+    // Whether the client picked an id or number, and so expects a reply
+    reply: bool,
+}
+
+impl Command {
+    // Parse the control data and payload that follow the `G`
+    fn parse(data: &[u8]) -> Self {
+        let (control, payload) = match data.iter().position(|&byte| byte == b';') {
+            Some(i) => (&data[..i], &data[i + 1..]),
+            None => (data, &[][..]),
+        };
+
+        let mut command = Self {
+            action: b't',
+            format: 32,
+            medium: b'd',
+            payload: payload.to_vec(),
+            ..Default::default()
+        };
+        for pair in control.split(|&byte| byte == b',') {
+            let Some((&key, value)) = pair.split_first() else {
+                continue;
+            };
+            let Some(value) = value.strip_prefix(b"=") else {
+                continue;
+            };
+            let char_value = value.first().copied().unwrap_or(0);
+            let number = || {
+                std::str::from_utf8(value)
+                    .ok()
+                    .and_then(|value| value.parse::<u32>().ok())
+                    .unwrap_or(0)
+            };
+            match key {
+                b'a' => command.action = char_value,
+                b'q' => command.quiet = number(),
+                b'f' => command.format = number(),
+                b't' => command.medium = char_value,
+                b'o' => command.compression = char_value,
+                b's' => command.width = number(),
+                b'v' => command.height = number(),
+                b'S' => command.size = number() as usize,
+                b'O' => command.offset = number().into(),
+                b'i' => command.id = number(),
+                b'I' => command.number = number(),
+                b'p' => command.placement_id = number(),
+                b'm' => command.more = number() == 1,
+                b'c' => command.columns = number(),
+                b'r' => command.rows = number(),
+                b'x' => command.source_x = number(),
+                b'y' => command.source_y = number(),
+                b'w' => command.source_width = number(),
+                b'h' => command.source_height = number(),
+                b'X' => command.offset_x = number(),
+                b'Y' => command.offset_y = number(),
+                b'z' => {
+                    command.z_index = std::str::from_utf8(value)
+                        .ok()
+                        .and_then(|value| value.parse().ok())
+                        .unwrap_or(0)
+                }
+                b'd' => command.delete = char_value,
+                _ => {}
+            }
+        }
+        command.reply = command.id != 0 || command.number != 0;
+        command
+    }
+}
+
+/// Identifies a placement made with the kitty graphics protocol
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct KittyPlacementId {
+    pub image_id: u32,
+    pub image_number: u32,
+    pub placement_id: u32,
+}
+
+/// Image to place at the cursor
+#[derive(Debug)]
+pub struct KittyPlacement {
+    pub ids: KittyPlacementId,
+    /// Pixels, already cropped to the source rectangle
+    pub image: Arc<RgbaImage>,
+    /// Size in cells, zero to use the size of the image
+    pub columns: u32,
+    pub rows: u32,
+    /// Offset in pixels inside the first cell
+    pub offset_x: u32,
+    pub offset_y: u32,
+    pub z_index: i32,
+}
+
+/// Placements to delete, see the `d` key of the protocol
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KittyDelete {
+    All,
+    Id {
+        image_id: u32,
+        placement_id: u32,
+    },
+    Number {
+        image_number: u32,
+        placement_id: u32,
+    },
+    IdRange {
+        start: u32,
+        end: u32,
+    },
+    ZIndex(i32),
+    /// Placements covering the cursor
+    Cursor,
+    /// Placements covering a cell, optionally with a z-index. Coordinates start at 1
+    Cell {
+        x: u32,
+        y: u32,
+        z_index: Option<i32>,
+    },
+    Column(u32),
+    Row(u32),
+}
+
+/// Result of a graphics command that the tap has to apply to the grid
+#[derive(Debug)]
+pub enum KittyAction {
+    Place(KittyPlacement),
+    Delete(KittyDelete),
+}
+
+struct StoredImage {
+    number: u32,
+    image: Arc<RgbaImage>,
+    // Used to evict the oldest images first
+    age: u64,
+}
+
+/// Stored images and the state of chunked transmissions
+#[derive(Default)]
+pub struct KittyGraphics {
+    images: HashMap<u32, StoredImage>,
+    next_id: u32,
+    next_age: u64,
+    // First command of a chunked transmission, with the payload received so far
+    chunk_opt: Option<Command>,
+    /// Replies to send back to the application
+    pub responses: Vec<u8>,
+}
+
+impl KittyGraphics {
+    /// Handle the contents of an APC sequence, starting after the `G`
+    pub fn command(&mut self, data: &[u8]) -> Option<KittyAction> {
+        let mut command = Command::parse(data);
+
+        if let Some(mut chunk) = self.chunk_opt.take() {
+            // Only the first chunk has the full control data
+            if chunk.payload.len() + command.payload.len() > MAX_PAYLOAD {
+                self.respond(&chunk, Err("EFBIG:payload too large"));
+                return None;
+            }
+            chunk.payload.append(&mut command.payload);
+            if command.more {
+                self.chunk_opt = Some(chunk);
+                return None;
+            }
+            command = chunk;
+        } else if command.more && matches!(command.action, b't' | b'T' | b'q') {
+            self.chunk_opt = Some(command);
+            return None;
+        }
+
+        match command.action {
+            b't' | b'T' | b'q' => {
+                let image = match self.load(&command) {
+                    Ok(image) => Arc::new(image),
+                    Err(err) => {
+                        self.respond(&command, Err(err));
+                        return None;
+                    }
+                };
+                if command.action == b'q' {
+                    // Queries only check that the image could be loaded
+                    self.respond(&command, Ok(()));
+                    return None;
+                }
+
+                if command.id == 0 {
+                    command.id = self.unused_id();
+                }
+                self.store(command.id, command.number, image.clone());
+                if command.action == b't' {
+                    self.respond(&command, Ok(()));
+                    None
+                } else {
+                    self.place(&command, image)
+                }
+            }
+            b'p' => {
+                let found = if command.id != 0 {
+                    self.images
+                        .get(&command.id)
+                        .map(|stored| (command.id, stored.image.clone()))
+                } else {
+                    self.newest_with_number(command.number)
+                };
+                let Some((id, image)) = found else {
+                    self.respond(&command, Err("ENOENT:image not found"));
+                    return None;
+                };
+                command.id = id;
+                self.place(&command, image)
+            }
+            b'd' => self.delete(&command).map(KittyAction::Delete),
+            _ => {
+                self.respond(&command, Err("EINVAL:unsupported action"));
+                None
+            }
+        }
+    }
+
+    fn place(&mut self, command: &Command, image: Arc<RgbaImage>) -> Option<KittyAction> {
+        let has_source_rect = command.source_x != 0
+            || command.source_y != 0
+            || command.source_width != 0
+            || command.source_height != 0;
+        let image = if has_source_rect {
+            match crop(&image, command) {
+                Some(cropped) => Arc::new(cropped),
+                None => {
+                    self.respond(command, Err("EINVAL:source rectangle outside of image"));
+                    return None;
+                }
+            }
+        } else {
+            image
+        };
+        self.respond(command, Ok(()));
+        Some(KittyAction::Place(KittyPlacement {
+            ids: KittyPlacementId {
+                image_id: command.id,
+                image_number: command.number,
+                placement_id: command.placement_id,
+            },
+            image,
+            columns: command.columns,
+            rows: command.rows,
+            offset_x: command.offset_x,
+            offset_y: command.offset_y,
+            z_index: command.z_index,
+        }))
+    }
+
+    fn delete(&mut self, command: &Command) -> Option<KittyDelete> {
+        // Upper case variants also free the image data
+        let free = command.delete.is_ascii_uppercase();
+        let delete = match command.delete.to_ascii_lowercase() {
+            0 | b'a' => KittyDelete::All,
+            b'i' => KittyDelete::Id {
+                image_id: command.id,
+                placement_id: command.placement_id,
+            },
+            b'n' => KittyDelete::Number {
+                image_number: command.number,
+                placement_id: command.placement_id,
+            },
+            b'r' => KittyDelete::IdRange {
+                start: command.source_x,
+                end: command.source_y,
+            },
+            b'z' => KittyDelete::ZIndex(command.z_index),
+            b'c' => KittyDelete::Cursor,
+            b'p' => KittyDelete::Cell {
+                x: command.source_x,
+                y: command.source_y,
+                z_index: None,
+            },
+            b'q' => KittyDelete::Cell {
+                x: command.source_x,
+                y: command.source_y,
+                z_index: Some(command.z_index),
+            },
+            b'x' => KittyDelete::Column(command.source_x),
+            b'y' => KittyDelete::Row(command.source_y),
+            _ => return None,
+        };
+
+        if free {
+            match delete {
+                KittyDelete::All => self.images.clear(),
+                KittyDelete::Id { image_id, .. } => {
+                    self.images.remove(&image_id);
+                }
+                KittyDelete::Number { image_number, .. } => {
+                    self.images.retain(|_, image| image.number != image_number);
+                }
+                KittyDelete::IdRange { start, end } => {
+                    self.images.retain(|id, _| !(start..=end).contains(id));
+                }
+                // Freeing by position would need to know where placements are
+                _ => {}
+            }
+        }
+        Some(delete)
+    }
+
+    // Read, decompress and decode the image data of a transmission
+    fn load(&self, command: &Command) -> Result<RgbaImage, &'static str> {
+        let payload = BASE64
+            .decode(&command.payload)
+            .map_err(|_| "EINVAL:invalid base64")?;
+        let data = match command.medium {
+            b'd' => payload,
+            b'f' | b't' => {
+                let path = String::from_utf8(payload).map_err(|_| "EINVAL:invalid path")?;
+                read_file(Path::new(&path), command)?
+            }
+            _ => return Err("EINVAL:unsupported transmission medium"),
+        };
+        let data = match command.compression {
+            0 => data,
+            b'z' => {
+                let mut decompressed = Vec::new();
+                ZlibDecoder::new(&data[..])
+                    .take(MAX_PAYLOAD as u64)
+                    .read_to_end(&mut decompressed)
+                    .map_err(|_| "EINVAL:invalid zlib data")?;
+                decompressed
+            }
+            _ => return Err("EINVAL:unsupported compression"),
+        };
+
+        match command.format {
+            24 | 32 => {
+                let (width, height) = (command.width, command.height);
+                if width == 0 || height == 0 || width > MAX_SIZE || height > MAX_SIZE {
+                    return Err("EINVAL:invalid image size");
+                }
+                let pixels = width as usize * height as usize;
+                let rgba = if command.format == 32 {
+                    data.get(..pixels * 4)
+                        .ok_or("ENODATA:not enough data")?
+                        .to_vec()
+                } else {
+                    let rgb = data.get(..pixels * 3).ok_or("ENODATA:not enough data")?;
+                    rgb.chunks_exact(3)
+                        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+                        .collect()
+                };
+                Ok(RgbaImage {
+                    width: width as usize,
+                    height: height as usize,
+                    rgba,
+                })
+            }
+            100 => match RgbaImage::decode(&data, Some(image::ImageFormat::Png)) {
+                Ok(image) => Ok(image),
+                Err(image::ImageError::Limits(_)) => Err("EINVAL:invalid image size"),
+                Err(_) => Err("EBADPNG:failed to decode PNG"),
+            },
+            _ => Err("EINVAL:unsupported format"),
+        }
+    }
+
+    fn store(&mut self, id: u32, number: u32, image: Arc<RgbaImage>) {
+        let age = self.next_age;
+        self.next_age += 1;
+        self.images.insert(id, StoredImage { number, image, age });
+
+        let mut used: usize = self
+            .images
+            .values()
+            .map(|image| image.image.rgba.len())
+            .sum();
+        while used > STORAGE_QUOTA {
+            let Some(oldest) = self
+                .images
+                .iter()
+                .min_by_key(|(_, image)| image.age)
+                .map(|(id, _)| *id)
+            else {
+                break;
+            };
+            if let Some(image) = self.images.remove(&oldest) {
+                used -= image.image.rgba.len();
+            }
+        }
+    }
+
+    fn newest_with_number(&self, number: u32) -> Option<(u32, Arc<RgbaImage>)> {
+        if number == 0 {
+            return None;
+        }
+        self.images
+            .iter()
+            .filter(|(_, image)| image.number == number)
+            .max_by_key(|(_, image)| image.age)
+            .map(|(id, image)| (*id, image.image.clone()))
+    }
+
+    // Ids chosen by the terminal count down from the top, away from ids picked by clients
+    fn unused_id(&mut self) -> u32 {
+        loop {
+            self.next_id = self.next_id.wrapping_sub(1);
+            if self.next_id != 0 && !self.images.contains_key(&self.next_id) {
+                return self.next_id;
+            }
+        }
+    }
+
+    fn respond(&mut self, command: &Command, result: Result<(), &str>) {
+        if !command.reply {
+            return;
+        }
+        match (&result, command.quiet) {
+            (Ok(()), 1..) | (Err(_), 2..) => return,
+            _ => {}
+        }
+
+        let mut keys = Vec::new();
+        if command.id != 0 {
+            keys.push(format!("i={}", command.id));
+        }
+        if command.number != 0 {
+            keys.push(format!("I={}", command.number));
+        }
+        if command.placement_id != 0 {
+            keys.push(format!("p={}", command.placement_id));
+        }
+        let message = match result {
+            Ok(()) => "OK",
+            Err(err) => err,
+        };
+        self.responses
+            .extend_from_slice(format!("\x1B_G{};{}\x1B\\", keys.join(","), message).as_bytes());
+    }
+}
+
+fn crop(image: &RgbaImage, command: &Command) -> Option<RgbaImage> {
+    let x = command.source_x as usize;
+    let y = command.source_y as usize;
+    if x >= image.width || y >= image.height {
+        return None;
+    }
+    let width = match command.source_width {
+        0 => image.width - x,
+        width => (width as usize).min(image.width - x),
+    };
+    let height = match command.source_height {
+        0 => image.height - y,
+        height => (height as usize).min(image.height - y),
+    };
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for row in y..y + height {
+        let start = (row * image.width + x) * 4;
+        rgba.extend_from_slice(&image.rgba[start..start + width * 4]);
+    }
+    Some(RgbaImage {
+        width,
+        height,
+        rgba,
+    })
+}
+
+fn read_file(path: &Path, command: &Command) -> Result<Vec<u8>, &'static str> {
+    // Resolve links and `..` first, so the checks below apply to the file that is read
+    let path = fs::canonicalize(path).map_err(|_| "ENOENT:file not found")?;
+    // Like kitty, refuse anything that is not a regular file, and pseudo file systems
+    let shm = Path::new("/dev/shm");
+    if ["/proc", "/sys", "/dev"]
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+        && !path.starts_with(shm)
+    {
+        return Err("EPERM:refusing to read from pseudo file system");
+    }
+    // Temporary files are deleted once read, so only accept ones clearly meant for this
+    let temporary = command.medium == b't';
+    if temporary {
+        let temp_dir = env::temp_dir();
+        let temp_dir = fs::canonicalize(&temp_dir).unwrap_or(temp_dir);
+        let in_temp_dir = path
+            .parent()
+            .map_or(false, |parent| parent == temp_dir || parent == shm);
+        let marked = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| name.contains("tty-graphics-protocol"));
+        if !in_temp_dir || !marked {
+            return Err("EPERM:not a temporary file for the graphics protocol");
+        }
+    }
+    let metadata = fs::metadata(&path).map_err(|_| "ENOENT:file not found")?;
+    if !metadata.is_file() {
+        return Err("EINVAL:not a regular file");
+    }
+
+    let mut file = File::open(&path).map_err(|_| "EPERM:failed to open file")?;
+    file.seek(SeekFrom::Start(command.offset))
+        .map_err(|_| "EINVAL:invalid offset")?;
+    let limit = match command.size {
+        0 => MAX_PAYLOAD,
+        size => size.min(MAX_PAYLOAD),
+    };
+    let mut data = Vec::new();
+    file.take(limit as u64)
+        .read_to_end(&mut data)
+        .map_err(|_| "EIO:failed to read file")?;
+
+    if temporary {
+        let _ = fs::remove_file(&path);
+    }
+    Ok(data)
+}
@@ -1,10 +1,11 @@
-use alacritty_terminal::term::cell::Flags;
+use alacritty_terminal::term::cell::{Flags, Hyperlink};
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Metadata {
     pub bg: cosmic_text::Color,
     pub underline_color: cosmic_text::Color,
     pub flags: Flags,
+    pub hyperlink: Option<Hyperlink>,
 }
 
 impl Metadata {
@@ -14,6 +15,7 @@ impl Metadata {
             bg,
             underline_color,
             flags,
+            hyperlink: None,
         }
     }
 
@@ -27,4 +29,8 @@ impl Metadata {
     pub(super) fn with_flags(self, flags: Flags) -> Self {
         Self { flags, ..self }
     }
+
+    pub(super) fn with_hyperlink(self, hyperlink: Option<Hyperlink>) -> Self {
+        Self { hyperlink, ..self }
+    }
 }
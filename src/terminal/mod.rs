@@ -1,3 +1,9 @@
 pub mod constants;
+pub mod glyphs;
+pub mod hints;
+pub mod iterm_image;
+pub mod kitty_graphics;
 pub mod metadata;
+pub mod sixel;
+pub mod tap;
 pub mod terminal;
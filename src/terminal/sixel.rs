@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+// Decoder for DEC sixel graphics, fed with the data bytes of a `DCS q` sequence
+
+use std::mem;
+
+use super::tap::RgbaImage;
+
+// Images larger than this in either direction are cropped
+pub(super) const MAX_SIZE: usize = 4096;
+// Pixels drawn for one image before it is dropped, so data drawing the same pixels over and
+// over cannot keep the PTY reader busy
+const MAX_DRAWN: usize = MAX_SIZE * MAX_SIZE * 4;
+// Number of color registers, matching what xterm advertises
+const PALETTE_SIZE: usize = 1024;
+
+// VT340 default color registers, in percent
+const VT340_PALETTE: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [20, 20, 80],
+    [80, 13, 13],
+    [20, 80, 20],
+    [80, 20, 80],
+    [20, 80, 80],
+    [80, 80, 20],
+    [53, 53, 53],
+    [26, 26, 26],
+    [33, 33, 60],
+    [60, 26, 26],
+    [33, 60, 33],
+    [60, 33, 60],
+    [33, 60, 60],
+    [60, 60, 33],
+    [80, 80, 80],
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Command {
+    Color,
+    Raster,
+    Repeat,
+}
+
+pub struct SixelDecoder {
+    transparent: bool,
+    palette: Vec<[u8; 4]>,
+    color: usize,
+    command_opt: Option<Command>,
+    params: Vec<usize>,
+    repeat: usize,
+    x: usize,
+    // Top of the current six pixel band
+    y: usize,
+    // Size used by the image so far, or set by raster attributes
+    width: usize,
+    height: usize,
+    // Size of the pixel buffer, which grows ahead of the image size
+    buffer_width: usize,
+    buffer_height: usize,
+    buffer: Vec<u8>,
+    drawn: usize,
+}
+
+impl SixelDecoder {
+    /// Create a decoder, `transparent` is set when the second DCS parameter is 1
+    pub fn new(transparent: bool) -> Self {
+        let mut palette = vec![[0, 0, 0, 255]; PALETTE_SIZE];
+        for (color, percent) in palette.iter_mut().zip(VT340_PALETTE.iter()) {
+            *color = [
+                percent_to_u8(percent[0].into()),
+                percent_to_u8(percent[1].into()),
+                percent_to_u8(percent[2].into()),
+                255,
+            ];
+        }
+        Self {
+            transparent,
+            palette,
+            color: 0,
+            command_opt: None,
+            params: Vec::new(),
+            repeat: 1,
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+            buffer_width: 0,
+            buffer_height: 0,
+            buffer: Vec::new(),
+            drawn: 0,
+        }
+    }
+
+    pub fn put(&mut self, byte: u8) {
+        if self.command_opt.is_some() {
+            match byte {
+                b'0'..=b'9' => {
+                    if self.params.is_empty() {
+                        self.params.push(0);
+                    }
+                    if let Some(param) = self.params.last_mut() {
+                        // Saturate well above any useful value
+                        *param = (*param * 10 + usize::from(byte - b'0')).min(1 << 20);
+                    }
+                    return;
+                }
+                b';' => {
+                    if self.params.is_empty() {
+                        self.params.push(0);
+                    }
+                    self.params.push(0);
+                    return;
+                }
+                _ => self.finish_command(),
+            }
+        }
+
+        match byte {
+            b'!' => self.command_opt = Some(Command::Repeat),
+            b'#' => self.command_opt = Some(Command::Color),
+            b'"' => self.command_opt = Some(Command::Raster),
+            b'$' => self.x = 0,
+            b'-' => {
+                self.x = 0;
+                self.y += 6;
+            }
+            b'?'..=b'~' => self.sixel(byte - b'?'),
+            _ => {}
+        }
+    }
+
+    fn finish_command(&mut self) {
+        let params = mem::take(&mut self.params);
+        match self.command_opt.take() {
+            Some(Command::Repeat) => {
+                self.repeat = params.first().copied().unwrap_or(1).max(1);
+            }
+            Some(Command::Color) => match params[..] {
+                [color] => self.color = color % PALETTE_SIZE,
+                [color, space, a, b, c] => {
+                    let color = color % PALETTE_SIZE;
+                    let rgb = match space {
+                        1 => hls_to_rgb(a, b, c),
+                        2 => [percent_to_u8(a), percent_to_u8(b), percent_to_u8(c)],
+                        _ => return,
+                    };
+                    self.palette[color] = [rgb[0], rgb[1], rgb[2], 255];
+                    self.color = color;
+                }
+                _ => {}
+            },
+            Some(Command::Raster) => {
+                if let [_pan, _pad, width, height] = params[..] {
+                    let width = width.min(MAX_SIZE);
+                    let height = height.min(MAX_SIZE);
+                    if self.reserve(width, height) {
+                        self.width = self.width.max(width);
+                        self.height = self.height.max(height);
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    // Grow the buffer to hold the given size, returns false if it is too large
+    fn reserve(&mut self, width: usize, height: usize) -> bool {
+        if width > MAX_SIZE || height > MAX_SIZE {
+            return false;
+        }
+        if width <= self.buffer_width && height <= self.buffer_height {
+            return true;
+        }
+
+        let buffer_width = width.max(self.buffer_width).next_power_of_two();
+        let buffer_height = height.max(self.buffer_height).next_power_of_two();
+        let mut buffer = vec![0; buffer_width * buffer_height * 4];
+        let row_len = self.buffer_width * 4;
+        for row in 0..self.buffer_height {
+            let src = row * row_len;
+            let dst = row * buffer_width * 4;
+            buffer[dst..dst + row_len].copy_from_slice(&self.buffer[src..src + row_len]);
+        }
+        self.buffer_width = buffer_width;
+        self.buffer_height = buffer_height;
+        self.buffer = buffer;
+        true
+    }
+
+    fn sixel(&mut self, bits: u8) {
+        let repeat = mem::replace(&mut self.repeat, 1);
+        let end_x = self.x.saturating_add(repeat);
+        self.drawn = self
+            .drawn
+            .saturating_add(repeat.saturating_mul(bits.count_ones() as usize));
+        if self.drawn > MAX_DRAWN || !self.reserve(end_x, self.y + 6) {
+            self.x = end_x;
+            return;
+        }
+
+        let color = self.palette[self.color];
+        for bit in 0..6 {
+            if bits & (1 << bit) == 0 {
+                continue;
+            }
+            let row = (self.y + bit) * self.buffer_width * 4;
+            for x in self.x..end_x {
+                let offset = row + x * 4;
+                self.buffer[offset..offset + 4].copy_from_slice(&color);
+            }
+            self.height = self.height.max(self.y + bit + 1);
+        }
+        self.width = self.width.max(end_x);
+        self.x = end_x;
+    }
+
+    /// Crop the decoded pixels to the image size, returns `None` for empty images
+    pub fn finish(mut self) -> Option<RgbaImage> {
+        self.finish_command();
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        if self.drawn > MAX_DRAWN {
+            log::warn!("sixel image draws too many pixels");
+            return None;
+        }
+
+        let background = self.palette[0];
+        let mut rgba = Vec::with_capacity(self.width * self.height * 4);
+        for row in 0..self.height {
+            let start = row * self.buffer_width * 4;
+            rgba.extend_from_slice(&self.buffer[start..start + self.width * 4]);
+        }
+        // Pixels that were never drawn are transparent, or the background color
+        if !self.transparent {
+            for pixel in rgba.chunks_exact_mut(4) {
+                if pixel[3] == 0 {
+                    pixel.copy_from_slice(&background);
+                }
+            }
+        }
+
+        Some(RgbaImage {
+            width: self.width,
+            height: self.height,
+            rgba,
+        })
+    }
+}
+
+fn percent_to_u8(percent: usize) -> u8 {
+    (percent.min(100) * 255 / 100) as u8
+}
+
+// Sixel HLS puts blue at 0 degrees, red at 120 and green at 240
+fn hls_to_rgb(hue: usize, lightness: usize, saturation: usize) -> [u8; 3] {
+    let hue = ((hue + 240) % 360) as f32;
+    let lightness = lightness.min(100) as f32 / 100.0;
+    let saturation = saturation.min(100) as f32 / 100.0;
+
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    [
+        ((r + m) * 255.0).round() as u8,
+        ((g + m) * 255.0).round() as u8,
+        ((b + m) * 255.0).round() as u8,
+    ]
+}
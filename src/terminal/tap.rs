@@ -0,0 +1,1032 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+// Reads PTY output before alacritty_terminal parses it, to pick up sequences it ignores.
+// The event loop holds the terminal lock while reading, so the tap only locks it between reads,
+// see `AnchorWait`.
+
+use alacritty_terminal::{
+    event::{Event as TermEvent, EventListener},
+    grid::Dimensions,
+    index::{Column, Point},
+    term::{Term, TermMode},
+    vte::{Params, Parser, Perform},
+};
+#[cfg(unix)]
+use alacritty_terminal::{
+    event::{OnResize, WindowSize},
+    sync::FairMutex,
+    tty::{self, ChildEvent, EventedPty, EventedReadWrite},
+};
+use base64::{
+    alphabet,
+    engine::{general_purpose::GeneralPurpose, DecodePaddingMode, GeneralPurposeConfig},
+};
+use image::{
+    error::{LimitError, LimitErrorKind},
+    io::{Limits, Reader as ImageReader},
+    ImageError, ImageFormat, ImageResult,
+};
+#[cfg(unix)]
+use polling::{Event, PollMode, Poller};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    io::{self, Cursor, Read},
+    mem,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+#[cfg(unix)]
+use std::{
+    fs::File,
+    io::{ErrorKind, Write},
+    os::unix::net::UnixStream,
+};
+
+use super::{
+    iterm_image::InlineImage,
+    kitty_graphics::{KittyAction, KittyDelete, KittyGraphics, KittyPlacement, KittyPlacementId},
+    sixel::SixelDecoder,
+    terminal::EventProxy,
+};
+
+// Least recently shown images are dropped when there are more than this
+const MAX_IMAGES: usize = 256;
+// or when their pixels take more than this many bytes
+const MAX_IMAGE_BYTES: usize = 256 * 1024 * 1024;
+// Longest APC sequence kept, larger ones are dropped
+const MAX_APC: usize = 64 * 1024 * 1024;
+// Image files larger than this in either direction are not decoded
+const MAX_DECODED_SIZE: u32 = 10000;
+// Nor are those whose pixels would take more than this many bytes
+const MAX_DECODED_BYTES: u64 = 256 * 1024 * 1024;
+// Longest the output waits for the terminal to report where an anchor is
+#[cfg(unix)]
+const ANCHOR_TIMEOUT: Duration = Duration::from_millis(100);
+// Key of PTY reads and writes in the event loop, which alacritty_terminal keeps private
+#[cfg(unix)]
+const PTY_READ_WRITE_TOKEN: usize = 0;
+
+/// Base64 used by image protocols, where clients are allowed to leave out padding
+pub(super) const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// FinalTerm/OSC 133 semantic prompt marks
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PromptMark {
+    /// `A`, start of the prompt
+    PromptStart,
+    /// `B`, start of the command line
+    CommandStart,
+    /// `C`, start of the command output
+    OutputStart,
+    /// `D`, command finished
+    CommandEnd,
+}
+
+impl PromptMark {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            b'A' => Some(Self::PromptStart),
+            b'B' => Some(Self::CommandStart),
+            b'C' => Some(Self::OutputStart),
+            b'D' => Some(Self::CommandEnd),
+            _ => None,
+        }
+    }
+}
+
+/// Prompt in the scrollback of the main screen, with lines counted from the top of the
+/// scrollback
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Prompt {
+    /// Id of its prompt start mark, which exit statuses are stored by
+    pub id: u64,
+    pub start: Point<usize>,
+    /// Where the output of its command starts, once it ran
+    pub output_opt: Option<Point<usize>>,
+}
+
+/// What the tap anchors to the cursor position at the point of the output where it was seen
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum AnchorKind {
+    /// Prompt start mark, with its id
+    Prompt(u64),
+    /// Command output start mark, with the id of its prompt
+    Output(u64),
+    /// Top left corner of an image, with its id
+    Image(u64),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) struct Anchor {
+    kind: AnchorKind,
+    /// Screen point reported by the terminal
+    point_opt: Option<Point<usize>>,
+}
+
+/// Desktop notification requested with OSC 9 or OSC 777
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Notification {
+    pub title: Option<String>,
+    pub body: String,
+}
+
+/// Command that ran between OSC 133 `C` and `D` marks
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FinishedCommand {
+    pub duration: Duration,
+    pub status: Option<i32>,
+}
+
+/// Decoded image
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RgbaImage {
+    pub width: usize,
+    pub height: usize,
+    /// Pixels in RGBA order, row by row
+    pub rgba: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Decode an image file, guessing its format if `format_opt` is `None`. The size is read
+    /// from the header first, so small files cannot make the decoder allocate huge images.
+    pub fn decode(data: &[u8], format_opt: Option<ImageFormat>) -> ImageResult<Self> {
+        let reader = || -> io::Result<ImageReader<Cursor<&[u8]>>> {
+            match format_opt {
+                Some(format) => Ok(ImageReader::with_format(Cursor::new(data), format)),
+                None => ImageReader::new(Cursor::new(data)).with_guessed_format(),
+            }
+        };
+
+        let (width, height) = reader()?.into_dimensions()?;
+        if width > MAX_DECODED_SIZE
+            || height > MAX_DECODED_SIZE
+            || u64::from(width) * u64::from(height) * 4 > MAX_DECODED_BYTES
+        {
+            return Err(ImageError::Limits(LimitError::from_kind(
+                LimitErrorKind::DimensionError,
+            )));
+        }
+
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_DECODED_SIZE);
+        limits.max_image_height = Some(MAX_DECODED_SIZE);
+        limits.max_alloc = Some(MAX_DECODED_BYTES);
+        let mut reader = reader()?;
+        reader.limits(limits);
+        let decoded = reader.decode()?.into_rgba8();
+        Ok(Self {
+            width: decoded.width() as usize,
+            height: decoded.height() as usize,
+            rgba: decoded.into_raw(),
+        })
+    }
+}
+
+/// Image placed in the grid
+#[derive(Debug)]
+pub struct TapImage {
+    pub image: Arc<RgbaImage>,
+    /// Offset from the anchor cell and size, in cells so images scale with the font size
+    pub x: f32,
+    pub y: f32,
+    pub columns: f32,
+    pub rows: f32,
+    /// Images with a negative z-index are drawn below the text
+    pub z_index: i32,
+    /// Set for placements made with the kitty graphics protocol
+    pub kitty_opt: Option<KittyPlacementId>,
+    /// When the image was placed or last visible, the least recently used is evicted first
+    pub last_used: Instant,
+    /// Cell of its top left corner, once the terminal reported it
+    pub anchor_opt: Option<ImageAnchor>,
+}
+
+/// Cell an image is placed at, with lines counted from the top of the scrollback of its screen
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ImageAnchor {
+    pub point: Point<usize>,
+    pub alt_screen: bool,
+}
+
+// Where the cursor goes after an image is placed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ImageCursor {
+    // Line below the image, like sixel scrolling mode
+    Below,
+    // After the last cell of the image, on its last line
+    After,
+    // Where it was before placing the image, leaving the cells alone
+    Keep,
+}
+
+/// State collected by the tap, shared with the [`Terminal`]
+///
+/// [`Terminal`]: super::terminal::Terminal
+#[derive(Debug, Default)]
+pub struct TapState {
+    /// Exit status of finished commands, by the id of their prompt start mark
+    pub exit_statuses: BTreeMap<u64, i32>,
+    /// Newest notification not yet taken by the application
+    pub notification_opt: Option<Notification>,
+    /// Finished commands not yet taken by the application
+    pub finished_commands: Vec<FinishedCommand>,
+    /// Images by id
+    pub images: HashMap<u64, TapImage>,
+    /// Cell size in pixels, set by the terminal to know how many cells images cover
+    pub cell_width: f32,
+    pub cell_height: f32,
+    /// Size of the terminal in cells, for images sized in percent
+    pub columns: usize,
+    pub screen_lines: usize,
+    /// Lines of scrollback, which bounds how many prompts can still be in the grid
+    pub history_lines: usize,
+    /// Kitty graphics deletions that depend on where images are, applied by the terminal
+    pub kitty_deletes: Vec<KittyDelete>,
+    /// Level of xterm's modifyOtherKeys set by the application, 0 when it is off
+    pub modify_other_keys: u8,
+    /// Prompts sorted by where they start. Lines that leave a full scrollback are not counted,
+    /// so only clearing it moves them up
+    pub prompts: Vec<Prompt>,
+    /// Marks waiting for the terminal to report where the cursor was, in order
+    pub(super) anchors: VecDeque<Anchor>,
+    /// Whether each cursor position report still to come was requested by the tap, in order
+    pub(super) position_reports: VecDeque<bool>,
+    /// Lines of scrollback when anchors were last resolved
+    pub(super) history_size: usize,
+}
+
+impl TapState {
+    /// Take a report of the terminal that answers a request of the tap, returning `false` for
+    /// other PTY writes
+    pub fn position_report(&mut self, text: &str) -> bool {
+        let Some((line, column)) = text
+            .strip_prefix("\x1B[")
+            .and_then(|report| report.strip_suffix('R'))
+            .and_then(|report| report.split_once(';'))
+        else {
+            return false;
+        };
+        let (Ok(line), Ok(column)) = (line.parse::<usize>(), column.parse::<usize>()) else {
+            return false;
+        };
+        if self.position_reports.pop_front() != Some(true) {
+            return false;
+        }
+        if let Some(anchor) = self
+            .anchors
+            .iter_mut()
+            .find(|anchor| anchor.point_opt.is_none())
+        {
+            // Reports count from 1
+            anchor.point_opt = Some(Point::new(
+                line.saturating_sub(1),
+                Column(column.saturating_sub(1)),
+            ));
+        }
+        true
+    }
+
+    /// Whether the terminal reported the position of every anchor
+    fn anchors_reported(&self) -> bool {
+        self.anchors.iter().all(|anchor| anchor.point_opt.is_some())
+    }
+
+    /// Count the lines of reported anchors from the top of the scrollback. This has to be done
+    /// before the terminal parses more output, which could scroll it.
+    pub fn resolve_anchors(&mut self, term: &Term<EventProxy>) {
+        let alt_screen = term.mode().contains(TermMode::ALT_SCREEN);
+        let history_size = term.grid().history_size();
+        if !alt_screen {
+            // Clearing the scrollback moves everything below it up
+            if history_size < self.history_size {
+                let removed = self.history_size - history_size;
+                self.prompts.retain_mut(|prompt| {
+                    let Some(line) = prompt.start.line.checked_sub(removed) else {
+                        return false;
+                    };
+                    prompt.start.line = line;
+                    prompt.output_opt = prompt.output_opt.and_then(|output| {
+                        Some(Point::new(output.line.checked_sub(removed)?, output.column))
+                    });
+                    true
+                });
+                self.images.retain(|_, image| match &mut image.anchor_opt {
+                    Some(anchor) if !anchor.alt_screen => {
+                        let Some(line) = anchor.point.line.checked_sub(removed) else {
+                            return false;
+                        };
+                        anchor.point.line = line;
+                        true
+                    }
+                    _ => true,
+                });
+            }
+            self.history_size = history_size;
+        }
+
+        while let Some(&Anchor {
+            kind,
+            point_opt: Some(point),
+        }) = self.anchors.front()
+        {
+            self.anchors.pop_front();
+            let point = Point::new(point.line + history_size, point.column);
+            match kind {
+                // Prompts are only tracked in the scrollback of the main screen
+                AnchorKind::Prompt(_) | AnchorKind::Output(_) if alt_screen => {}
+                AnchorKind::Prompt(id) => {
+                    // A prompt replaces the ones it is drawn over, like when the shell redraws it
+                    let count = self
+                        .prompts
+                        .partition_point(|prompt| prompt.start.line < point.line);
+                    self.prompts.truncate(count);
+                    self.prompts.push(Prompt {
+                        id,
+                        start: point,
+                        output_opt: None,
+                    });
+                    let max_prompts = self.history_lines + self.screen_lines;
+                    if self.prompts.len() > max_prompts {
+                        self.prompts.drain(..self.prompts.len() - max_prompts);
+                    }
+                }
+                AnchorKind::Output(id) => {
+                    if let Some(prompt) = self.prompts.iter_mut().rev().find(|p| p.id == id) {
+                        prompt.output_opt = Some(point);
+                    }
+                }
+                AnchorKind::Image(id) => {
+                    if let Some(image) = self.images.get_mut(&id) {
+                        image.anchor_opt = Some(ImageAnchor { point, alt_screen });
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct TapPerformer {
+    state: Arc<Mutex<TapState>>,
+    // Bytes to insert after the current input byte
+    inject: Vec<u8>,
+    // Set when something was anchored, the output stops there until its position is known
+    anchored: bool,
+    // Bytes to insert once the anchor is resolved, as they may move the cursor
+    deferred: Vec<u8>,
+    // Set while the application holds back a synchronized update
+    synchronized: bool,
+    next_mark_id: u64,
+    prompt_id_opt: Option<u64>,
+    command_start_opt: Option<Instant>,
+    next_image_id: u64,
+    sixel_opt: Option<SixelDecoder>,
+    kitty: KittyGraphics,
+    // Set when a sequence is dispatched, which returns the parser to the ground state
+    dispatched: bool,
+}
+
+impl TapPerformer {
+    /// Ask the terminal where the cursor is once it parsed the output up to here
+    fn anchor(&mut self, kind: AnchorKind) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.anchors.push_back(Anchor {
+                kind,
+                point_opt: None,
+            });
+            state.position_reports.push_back(true);
+        }
+        self.inject.extend_from_slice(b"\x1B[6n");
+        self.anchored = true;
+    }
+
+    /// Store an image and anchor it at the cursor
+    fn place_image(&mut self, image: TapImage, cursor: ImageCursor) {
+        let columns = (image.x + image.columns).ceil().max(1.0) as usize;
+        let rows = (image.y + image.rows).ceil().max(1.0) as usize;
+        {
+            let mut state = self.state.lock().unwrap();
+            let mut used: usize = state
+                .images
+                .values()
+                .map(|image| image.image.rgba.len())
+                .sum();
+            while state.images.len() >= MAX_IMAGES
+                || (!state.images.is_empty() && used + image.image.rgba.len() > MAX_IMAGE_BYTES)
+            {
+                let Some(lru) = state
+                    .images
+                    .iter()
+                    .min_by_key(|(_, image)| image.last_used)
+                    .map(|(id, _)| *id)
+                else {
+                    break;
+                };
+                if let Some(evicted) = state.images.remove(&lru) {
+                    used -= evicted.image.rgba.len();
+                }
+            }
+            state.images.insert(self.next_image_id, image);
+        }
+        self.anchor(AnchorKind::Image(self.next_image_id));
+        self.next_image_id += 1;
+
+        if cursor == ImageCursor::Keep {
+            return;
+        }
+        // The image replaces the cells it covers, and the cursor moves over it
+        for row in 0..rows {
+            self.deferred
+                .extend_from_slice(format!("\x1B[{}X", columns).as_bytes());
+            if row + 1 < rows || cursor == ImageCursor::Below {
+                self.deferred.extend_from_slice(b"\x1BD");
+            }
+        }
+        if cursor == ImageCursor::After {
+            self.deferred
+                .extend_from_slice(format!("\x1B[{}C", columns).as_bytes());
+        }
+    }
+
+    fn place_sixel(&mut self, image: RgbaImage) {
+        let (cell_width, cell_height) = {
+            let state = self.state.lock().unwrap();
+            (state.cell_width, state.cell_height)
+        };
+        if cell_width <= 0.0 || cell_height <= 0.0 {
+            return;
+        }
+        let image = TapImage {
+            x: 0.0,
+            y: 0.0,
+            columns: image.width as f32 / cell_width,
+            rows: image.height as f32 / cell_height,
+            image: Arc::new(image),
+            // Sixels replace the cells they cover, so they go below the text
+            z_index: -1,
+            kitty_opt: None,
+            last_used: Instant::now(),
+            anchor_opt: None,
+        };
+        self.place_image(image, ImageCursor::Below);
+    }
+
+    fn place_inline(&mut self, inline: InlineImage) {
+        let (cell_width, cell_height, columns, screen_lines) = {
+            let state = self.state.lock().unwrap();
+            (
+                state.cell_width,
+                state.cell_height,
+                state.columns,
+                state.screen_lines,
+            )
+        };
+        if cell_width <= 0.0 || cell_height <= 0.0 {
+            return;
+        }
+        let (width, height) = inline.display_size(cell_width, cell_height, columns, screen_lines);
+        let image = TapImage {
+            image: Arc::new(inline.image),
+            x: 0.0,
+            y: 0.0,
+            columns: width / cell_width,
+            rows: height / cell_height,
+            // Like sixels, inline images replace the cells they cover
+            z_index: -1,
+            kitty_opt: None,
+            last_used: Instant::now(),
+            anchor_opt: None,
+        };
+        self.place_image(image, ImageCursor::After);
+    }
+
+    fn place_kitty(&mut self, placement: KittyPlacement) {
+        let (cell_width, cell_height) = {
+            let state = self.state.lock().unwrap();
+            (state.cell_width, state.cell_height)
+        };
+        if cell_width <= 0.0 || cell_height <= 0.0 {
+            return;
+        }
+        let (width, height) = (placement.image.width as f32, placement.image.height as f32);
+        // A single given dimension scales the other one to keep the aspect ratio
+        let (columns, rows) = match (placement.columns, placement.rows) {
+            (0, 0) => (width / cell_width, height / cell_height),
+            (0, rows) => {
+                let rows = rows as f32;
+                (rows * cell_height * width / height / cell_width, rows)
+            }
+            (columns, 0) => {
+                let columns = columns as f32;
+                (columns, columns * cell_width * height / width / cell_height)
+            }
+            (columns, rows) => (columns as f32, rows as f32),
+        };
+        let image = TapImage {
+            image: placement.image,
+            x: (placement.offset_x as f32 / cell_width).min(1.0),
+            y: (placement.offset_y as f32 / cell_height).min(1.0),
+            columns,
+            rows,
+            z_index: placement.z_index,
+            kitty_opt: Some(placement.ids),
+            last_used: Instant::now(),
+            anchor_opt: None,
+        };
+        // Placements are drawn over the cells without writing to them, so the cursor stays
+        self.place_image(image, ImageCursor::Keep);
+    }
+
+    fn delete_kitty(&mut self, delete: KittyDelete) {
+        let mut state = self.state.lock().unwrap();
+        let matches: Box<dyn Fn(&KittyPlacementId, i32) -> bool> = match delete {
+            KittyDelete::All => Box::new(|_, _| true),
+            KittyDelete::Id {
+                image_id,
+                placement_id,
+            } => Box::new(move |ids, _| {
+                ids.image_id == image_id && (placement_id == 0 || ids.placement_id == placement_id)
+            }),
+            KittyDelete::Number {
+                image_number,
+                placement_id,
+            } => Box::new(move |ids, _| {
+                ids.image_number == image_number
+                    && (placement_id == 0 || ids.placement_id == placement_id)
+            }),
+            KittyDelete::IdRange { start, end } => {
+                Box::new(move |ids, _| (start..=end).contains(&ids.image_id))
+            }
+            KittyDelete::ZIndex(z_index) => Box::new(move |_, z| z == z_index),
+            // The tap does not know where images are, so the terminal handles the rest
+            _ => {
+                state.kitty_deletes.push(delete);
+                return;
+            }
+        };
+        state.images.retain(|_, image| match &image.kitty_opt {
+            Some(ids) => !matches(ids, image.z_index),
+            None => true,
+        });
+    }
+
+    fn notify(&mut self, notification: Notification) {
+        // Only the newest is kept, so a flood does not pile up while the application is limited
+        self.state.lock().unwrap().notification_opt = Some(notification);
+    }
+
+    /// Handle an APC sequence, which the parser ignores
+    fn apc_dispatch(&mut self, data: &[u8]) {
+        let Some(data) = data.strip_prefix(b"G") else {
+            return;
+        };
+        match self.kitty.command(data) {
+            Some(KittyAction::Place(placement)) => self.place_kitty(placement),
+            Some(KittyAction::Delete(delete)) => self.delete_kitty(delete),
+            None => {}
+        }
+    }
+}
+
+impl Perform for TapPerformer {
+    fn hook(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
+        if action == 'q' && intermediates.is_empty() {
+            // The second parameter selects whether unset pixels are transparent
+            let background_select = params
+                .iter()
+                .nth(1)
+                .and_then(|param| param.first())
+                .copied()
+                .unwrap_or(0);
+            self.sixel_opt = Some(SixelDecoder::new(background_select == 1));
+        }
+    }
+
+    fn put(&mut self, byte: u8) {
+        if let Some(sixel) = &mut self.sixel_opt {
+            sixel.put(byte);
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
+        self.dispatched = true;
+        let mut params = params
+            .iter()
+            .map(|param| param.first().copied().unwrap_or(0));
+        match (intermediates, action) {
+            // Reports requested by the application are told apart from the tap's by their order
+            (b"", 'n') => {
+                if params.next() == Some(6) {
+                    self.state.lock().unwrap().position_reports.push_back(false);
+                }
+            }
+            // The terminal only parses a synchronized update once it ends
+            (b"?", 'h' | 'l') => {
+                if params.any(|param| param == 2026) {
+                    self.synchronized = action == 'h';
+                }
+            }
+            // xterm's modifyOtherKeys is set with `CSI > 4 ; level m` and reset with
+            // `CSI > 4 n`, alacritty_terminal ignores both
+            (b">", 'm' | 'n') => {
+                if params.next() != Some(4) {
+                    return;
+                }
+                let level = match action {
+                    'm' => params.next().unwrap_or(0).min(2) as u8,
+                    _ => 0,
+                };
+                self.state.lock().unwrap().modify_other_keys = level;
+            }
+            _ => {}
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        self.dispatched = true;
+        // A full reset turns modifyOtherKeys off
+        if intermediates.is_empty() && byte == b'c' {
+            self.state.lock().unwrap().modify_other_keys = 0;
+        }
+    }
+
+    fn unhook(&mut self) {
+        if let Some(image) = self.sixel_opt.take().and_then(SixelDecoder::finish) {
+            self.place_sixel(image);
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        self.dispatched = true;
+        match params {
+            [b"133", kind, rest @ ..] => {
+                let Some(kind) = kind.first().and_then(|byte| PromptMark::from_byte(*byte)) else {
+                    return;
+                };
+                let id = self.next_mark_id;
+                self.next_mark_id += 1;
+                match kind {
+                    PromptMark::PromptStart => {
+                        self.prompt_id_opt = Some(id);
+                        self.anchor(AnchorKind::Prompt(id));
+                    }
+                    PromptMark::OutputStart => {
+                        self.command_start_opt = Some(Instant::now());
+                        if let Some(prompt_id) = self.prompt_id_opt {
+                            self.anchor(AnchorKind::Output(prompt_id));
+                        }
+                    }
+                    PromptMark::CommandEnd => {
+                        let status_opt = rest
+                            .first()
+                            .and_then(|status| std::str::from_utf8(status).ok())
+                            .and_then(|status| status.parse().ok());
+                        let mut state = self.state.lock().unwrap();
+                        if let (Some(prompt_id), Some(status)) = (self.prompt_id_opt, status_opt) {
+                            state.exit_statuses.insert(prompt_id, status);
+                            // Every prompt takes a line, so older ones left the scrollback
+                            let max_prompts = state.history_lines + state.screen_lines;
+                            while state.exit_statuses.len() > max_prompts {
+                                state.exit_statuses.pop_first();
+                            }
+                        }
+                        // Shells send D before every prompt, only count it after a command ran
+                        if let Some(command_start) = self.command_start_opt.take() {
+                            state.finished_commands.push(FinishedCommand {
+                                duration: command_start.elapsed(),
+                                status: status_opt,
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            // ConEmu uses OSC 9 with a numeric subcommand for other things, like progress
+            [b"9", kind, ..] if !kind.is_empty() && kind.iter().all(u8::is_ascii_digit) => {}
+            [b"9", body @ ..] if !body.is_empty() => {
+                self.notify(Notification {
+                    title: None,
+                    body: join_params(body),
+                });
+            }
+            [b"777", b"notify", title, body @ ..] => {
+                self.notify(Notification {
+                    title: Some(String::from_utf8_lossy(title).into_owned()),
+                    body: join_params(body),
+                });
+            }
+            [b"1337", file, ..] if file.starts_with(b"File=") => {
+                // Arguments are separated by semicolons, which the parser split on
+                let file = params[1..].join(&b';');
+                if let Some(inline) = InlineImage::parse(&file[b"File=".len()..]) {
+                    self.place_inline(inline);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// The parser splits OSC strings on semicolons, which are allowed in notification text
+fn join_params(params: &[&[u8]]) -> String {
+    String::from_utf8_lossy(&params.join(&b';')).into_owned()
+}
+
+// Collects APC sequences, which the parser skips without dispatching
+#[derive(Default)]
+enum ApcScanner {
+    #[default]
+    Ground,
+    Escape,
+    Apc(Vec<u8>),
+    ApcEscape(Vec<u8>),
+}
+
+impl ApcScanner {
+    // Returns the contents of an APC sequence when its ST is seen
+    fn advance(&mut self, byte: u8) -> Option<Vec<u8>> {
+        let (state, apc_opt) = match (mem::take(self), byte) {
+            (Self::Ground | Self::Escape, 0x1B) => (Self::Escape, None),
+            (Self::Escape, b'_') => (Self::Apc(Vec::new()), None),
+            (Self::Ground | Self::Escape, _) => (Self::Ground, None),
+            (Self::Apc(data) | Self::ApcEscape(data), 0x1B) => (Self::ApcEscape(data), None),
+            (Self::ApcEscape(data), b'\\') => (Self::Ground, Some(data)),
+            // Any other escape ends the sequence without dispatching it
+            (Self::ApcEscape(_), _) => (Self::Ground, None),
+            // Cancelled, like the parser does
+            (Self::Apc(_), 0x18 | 0x1A) => (Self::Ground, None),
+            (Self::Apc(mut data), _) => {
+                if data.len() < MAX_APC {
+                    data.push(byte);
+                }
+                (Self::Apc(data), None)
+            }
+        };
+        *self = state;
+        apc_opt
+    }
+}
+
+// Where the reader is in resolving anchors. The event loop keeps the terminal locked while it
+// reads and parses, so the reader stops the output at an anchor, waits for the terminal to report
+// the cursor position, and makes the event loop release the lock by having nothing to read. The
+// next read then knows that the terminal is unlocked and that nothing was parsed after the report.
+#[cfg(unix)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum AnchorWait {
+    None,
+    // Waiting for the report since the given time
+    Report(Instant),
+    // Waiting for the event loop to read again
+    Resolve,
+}
+
+#[cfg(unix)]
+pub struct TapReader {
+    file: File,
+    term: Arc<FairMutex<Term<EventProxy>>>,
+    event_proxy: EventProxy,
+    // Registered with the event loop, which reads again when a byte is written to `wake_tx`
+    wake_rx: UnixStream,
+    wake_tx: UnixStream,
+    parser: Parser,
+    // Whether the parser is known to be between sequences, where text can skip it
+    ground: bool,
+    performer: TapPerformer,
+    apc: ApcScanner,
+    input: Vec<u8>,
+    input_pos: usize,
+    output: Vec<u8>,
+    output_pos: usize,
+    wait: AnchorWait,
+}
+
+#[cfg(unix)]
+impl TapReader {
+    fn wake(&mut self) {
+        let _ = self.wake_tx.write(&[0]);
+    }
+
+    // Pass input through the tap's parser into the output, until the end or an anchor
+    fn process(&mut self) {
+        while self.input_pos < self.input.len() {
+            // Text between sequences does not matter to the tap
+            if self.ground && matches!(self.apc, ApcScanner::Ground) {
+                let input = &self.input[self.input_pos..];
+                let text = input
+                    .iter()
+                    .position(|&byte| byte == 0x1B)
+                    .unwrap_or(input.len());
+                self.output.extend_from_slice(&input[..text]);
+                self.input_pos += text;
+                if self.input_pos >= self.input.len() {
+                    break;
+                }
+            }
+
+            let byte = self.input[self.input_pos];
+            self.input_pos += 1;
+            self.output.push(byte);
+            self.parser.advance(&mut self.performer, byte);
+            if let Some(apc) = self.apc.advance(byte) {
+                self.performer.apc_dispatch(&apc);
+            }
+            // Sequences ended by ST are dispatched on its ESC, so wait for the backslash
+            if byte == 0x1B {
+                self.ground = false;
+                self.performer.dispatched = false;
+            } else {
+                self.output.append(&mut self.performer.inject);
+                if mem::take(&mut self.performer.dispatched) {
+                    self.ground = true;
+                }
+                if mem::take(&mut self.performer.anchored) {
+                    // The terminal reports nothing until a synchronized update ends, so anchors
+                    // in one are resolved later by the terminal
+                    if self.performer.synchronized {
+                        self.output.append(&mut self.performer.deferred);
+                    } else {
+                        self.wait = AnchorWait::Report(Instant::now());
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Replies are written by the event loop, in order with other input
+        let responses = mem::take(&mut self.performer.kitty.responses);
+        if !responses.is_empty() {
+            self.event_proxy.send_event(TermEvent::PtyWrite(
+                String::from_utf8_lossy(&responses).into_owned(),
+            ));
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Read for TapReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Wake ups only serve to get here
+        let mut wake = [0; 64];
+        while matches!(self.wake_rx.read(&mut wake), Ok(count) if count > 0) {}
+
+        while self.output_pos >= self.output.len() {
+            self.output.clear();
+            self.output_pos = 0;
+
+            match self.wait {
+                AnchorWait::None => {}
+                AnchorWait::Report(since) => {
+                    let reported = self.performer.state.lock().unwrap().anchors_reported();
+                    if reported || since.elapsed() < ANCHOR_TIMEOUT {
+                        if reported {
+                            // Everything read was parsed, so having nothing to read ends the
+                            // event loop's read and releases the terminal
+                            self.wait = AnchorWait::Resolve;
+                        }
+                        self.wake();
+                        return Err(ErrorKind::WouldBlock.into());
+                    }
+                    // Keep going, the terminal resolves the anchor if the report comes
+                    self.wait = AnchorWait::None;
+                    self.output.append(&mut self.performer.deferred);
+                }
+                AnchorWait::Resolve => {
+                    let term = self.term.lock_unfair();
+                    self.performer.state.lock().unwrap().resolve_anchors(&term);
+                    self.wait = AnchorWait::None;
+                    self.output.append(&mut self.performer.deferred);
+                }
+            }
+
+            if self.input_pos >= self.input.len() && self.output.is_empty() {
+                self.input.resize(4096, 0);
+                let count = self.file.read(&mut self.input)?;
+                self.input.truncate(count);
+                self.input_pos = 0;
+                if count == 0 {
+                    return Ok(0);
+                }
+            }
+            self.process();
+        }
+
+        let count = buf.len().min(self.output.len() - self.output_pos);
+        buf[..count].copy_from_slice(&self.output[self.output_pos..self.output_pos + count]);
+        self.output_pos += count;
+        Ok(count)
+    }
+}
+
+/// Wraps a [`tty::Pty`] so that its output passes through a [`TapReader`]
+#[cfg(unix)]
+pub struct PtyTap {
+    pty: tty::Pty,
+    reader: TapReader,
+}
+
+#[cfg(unix)]
+impl PtyTap {
+    pub fn new(
+        pty: tty::Pty,
+        term: Arc<FairMutex<Term<EventProxy>>>,
+        state: Arc<Mutex<TapState>>,
+        event_proxy: EventProxy,
+    ) -> io::Result<Self> {
+        let file = pty.file().try_clone()?;
+        let (wake_rx, wake_tx) = UnixStream::pair()?;
+        wake_rx.set_nonblocking(true)?;
+        wake_tx.set_nonblocking(true)?;
+        Ok(Self {
+            pty,
+            reader: TapReader {
+                file,
+                term,
+                event_proxy,
+                wake_rx,
+                wake_tx,
+                parser: Parser::new(),
+                ground: true,
+                performer: TapPerformer {
+                    state,
+                    inject: Vec::new(),
+                    anchored: false,
+                    deferred: Vec::new(),
+                    synchronized: false,
+                    next_mark_id: 0,
+                    prompt_id_opt: None,
+                    command_start_opt: None,
+                    next_image_id: 0,
+                    sixel_opt: None,
+                    kitty: KittyGraphics::default(),
+                    dispatched: false,
+                },
+                apc: ApcScanner::default(),
+                input: Vec::new(),
+                input_pos: 0,
+                output: Vec::new(),
+                output_pos: 0,
+                wait: AnchorWait::None,
+            },
+        })
+    }
+}
+
+#[cfg(unix)]
+impl EventedReadWrite for PtyTap {
+    type Reader = TapReader;
+    type Writer = <tty::Pty as EventedReadWrite>::Writer;
+
+    unsafe fn register(
+        &mut self,
+        poll: &Arc<Poller>,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.pty.register(poll, interest, mode)?;
+        poll.add_with_mode(
+            &self.reader.wake_rx,
+            Event::readable(PTY_READ_WRITE_TOKEN),
+            PollMode::Level,
+        )
+    }
+
+    fn reregister(
+        &mut self,
+        poll: &Arc<Poller>,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.pty.reregister(poll, interest, mode)
+    }
+
+    fn deregister(&mut self, poll: &Arc<Poller>) -> io::Result<()> {
+        self.pty.deregister(poll)?;
+        poll.delete(&self.reader.wake_rx)
+    }
+
+    fn reader(&mut self) -> &mut Self::Reader {
+        &mut self.reader
+    }
+
+    fn writer(&mut self) -> &mut Self::Writer {
+        self.pty.writer()
+    }
+}
+
+#[cfg(unix)]
+impl EventedPty for PtyTap {
+    fn next_child_event(&mut self) -> Option<ChildEvent> {
+        self.pty.next_child_event()
+    }
+}
+
+#[cfg(unix)]
+impl OnResize for PtyTap {
+    fn on_resize(&mut self, window_size: WindowSize) {
+        self.pty.on_resize(window_size)
+    }
+}
@@ -3,50 +3,105 @@ use alacritty_terminal::{
     event_loop::{EventLoop, Msg, Notifier},
     grid::Dimensions,
     index::{Boundary, Column, Direction, Line, Point, Side},
-    selection::{Selection, SelectionType},
+    selection::{Selection, SelectionRange, SelectionType},
     sync::FairMutex,
     term::{
         cell::Flags,
         color::{self, Colors},
         search::RegexSearch,
-        viewport_to_point, Config, TermDamage, TermMode,
+        viewport_to_point, ClipboardType, Config, TermDamage, TermMode,
     },
     tty::{self, Options},
-    vte::ansi::{Color, CursorShape, NamedColor, Rgb},
+    vi_mode::ViMotion,
+    vte::ansi::{Color, CursorShape, CursorStyle, NamedColor, Rgb},
     Term,
 };
 use cosmic::{
     iced::advanced::graphics::text::font_system,
     iced::mouse::ScrollDelta,
+    iced_core::image,
     widget::{pane_grid, segmented_button},
 };
 use cosmic_text::{
-    Attrs, AttrsList, Buffer, BufferLine, CacheKeyFlags, Family, LineEnding, Metrics, Shaping,
-    Weight, Wrap,
+    fontdb, Attrs, AttrsList, Buffer, BufferLine, CacheKeyFlags, Family, LineEnding, Metrics,
+    Shaping, Weight, Wrap,
 };
 use indexmap::IndexSet;
 use std::{
     borrow::Cow,
     collections::HashMap,
-    io, mem,
-    sync::{atomic::Ordering, Arc, Weak},
-    time::Instant,
+    fs, io, mem,
+    path::PathBuf,
+    sync::{atomic::Ordering, Arc, Mutex, Weak},
+    thread,
+    time::{Duration, Instant},
 };
 use tokio::sync::mpsc;
 
 pub use alacritty_terminal::grid::Scroll as TerminalScroll;
 
 use crate::{
-    config::{color_scheme::ColorSchemeKind, config::Config as AppConfig, profile::ProfileId},
+    config::{
+        color_scheme::ColorSchemeKind,
+        config::{Config as AppConfig, CursorShape as AppCursorShape},
+        profile::ProfileId,
+    },
     mouse_reporter::MouseReporter,
+    terminal_theme,
 };
 
-use super::{constants::WINDOW_BG_COLOR, metadata::Metadata};
+use super::{
+    constants::WINDOW_BG_COLOR,
+    glyphs,
+    hints::{self, Hint, HintKind, HintRegexes},
+    kitty_graphics::KittyDelete,
+    metadata::Metadata,
+    tap::{self, FinishedCommand, Notification, RgbaImage, TapState},
+};
 
 /// Minimum contrast between a fixed cursor color and the cell's background.
 /// Duplicated from alacritty
 pub const MIN_CURSOR_CONTRAST: f64 = 1.5;
 
+/// Number of distinct cell styles kept before the metadata set is rebuilt from scratch.
+const MAX_METADATA: usize = 4096;
+
+/// Number of adjusted colors kept for the minimum contrast before the cache is cleared.
+const MAX_MIN_CONTRAST_CACHE: usize = 4096;
+
+/// Image drawn over the grid, in pixels relative to the top left of the view
+#[derive(Clone, Debug)]
+pub struct VisibleImage {
+    pub handle: image::Handle,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Images with a negative z-index are drawn below the text
+    pub z_index: i32,
+}
+
+// Pixels of a tap image, shared with the image handle instead of copied
+struct ImagePixels(Arc<RgbaImage>);
+
+impl AsRef<[u8]> for ImagePixels {
+    fn as_ref(&self) -> &[u8] {
+        &self.0.rgba
+    }
+}
+
+// Image with a handle ready for drawing, with its position and size in cells
+#[derive(Debug)]
+struct PlacedImage {
+    handle: image::Handle,
+    x: f32,
+    y: f32,
+    columns: f32,
+    rows: f32,
+    z_index: i32,
+    kitty: bool,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Size {
     pub width: u32,
@@ -85,10 +140,17 @@ pub struct EventProxy(
     pane_grid::Pane,
     segmented_button::Entity,
     mpsc::Sender<(pane_grid::Pane, segmented_button::Entity, Event)>,
+    Arc<Mutex<TapState>>,
 );
 
 impl EventListener for EventProxy {
     fn send_event(&self, event: Event) {
+        // Cursor positions requested by the tap are reported to it instead of the PTY
+        if let Event::PtyWrite(text) = &event {
+            if self.3.lock().unwrap().position_report(text) {
+                return;
+            }
+        }
         //TODO: handle error
         let _ = self.2.blocking_send((self.0, self.1, event));
     }
@@ -135,6 +197,41 @@ fn convert_color(colors: &Colors, color: Color) -> cosmic_text::Color {
     cosmic_text::Color::rgb(rgb.r, rgb.g, rgb.b)
 }
 
+// Index of the first fallback font with a glyph for `c`, if the main font has none
+fn font_fallback(
+    cache: &mut HashMap<char, Option<usize>>,
+    fallbacks: &[String],
+    attrs: Attrs,
+    c: char,
+) -> Option<usize> {
+    if fallbacks.is_empty() || c.is_ascii() {
+        return None;
+    }
+    *cache.entry(c).or_insert_with(|| {
+        let mut font_system = font_system().write().unwrap();
+        let font_system = font_system.raw();
+        let mut has_glyph = |family: Family| {
+            let query = fontdb::Query {
+                families: &[family],
+                weight: attrs.weight,
+                stretch: attrs.stretch,
+                style: attrs.style,
+            };
+            font_system
+                .db()
+                .query(&query)
+                .and_then(|id| font_system.get_font(id))
+                .is_some_and(|font| font.rustybuzz().glyph_index(c).is_some())
+        };
+        if has_glyph(attrs.family) {
+            return None;
+        }
+        fallbacks
+            .iter()
+            .position(|fallback| has_glyph(Family::Name(fallback)))
+    })
+}
+
 type TabModel = segmented_button::Model<segmented_button::SingleSelect>;
 
 pub struct TerminalPaneGrid {
@@ -163,6 +260,13 @@ impl TerminalPaneGrid {
     }
 }
 
+// How long the visual bell takes to fade out
+const BELL_FLASH_DURATION: Duration = Duration::from_millis(200);
+// Bells closer together than this are ignored, so a bell loop cannot strobe the screen
+const BELL_RATE_LIMIT: Duration = Duration::from_millis(500);
+// Notifications closer together than this are dropped, so a loop cannot flood the desktop
+const NOTIFICATION_RATE_LIMIT: Duration = Duration::from_secs(1);
+
 pub struct Terminal {
     pub context_menu: Option<cosmic::iced::Point>,
     pub metadata_set: IndexSet<Metadata>,
@@ -170,16 +274,52 @@ pub struct Terminal {
     pub profile_id_opt: Option<ProfileId>,
     pub tab_title_override: Option<String>,
     pub term: Arc<FairMutex<Term<EventProxy>>>,
+    bell_flash_opt: Option<Instant>,
+    bell_last_opt: Option<Instant>,
     bold_font_weight: Weight,
     buffer: Arc<Buffer>,
     colors: Colors,
+    cursor_blink_interval: Duration,
+    cursor_blink_start: Instant,
+    cursor_blink_timeout_opt: Option<Duration>,
+    cursor_text_opt: Option<cosmic_text::Color>,
+    // Screen lines to rebuild on the next update besides the ones damaged by the terminal
+    damaged_lines: Vec<usize>,
     default_attrs: Attrs<'static>,
     dim_font_weight: Weight,
+    event_proxy: EventProxy,
+    focused: bool,
+    // Fallback font for each character looked up so far
+    font_fallback_cache: HashMap<char, Option<usize>>,
+    font_fallbacks: Vec<String>,
+    font_name: String,
+    full_update: bool,
+    hint_highlight: bool,
+    hint_regexes: HintRegexes,
+    hovered_hint: Option<Hint>,
+    hyperlink_tooltip_opt: Option<Arc<Buffer>>,
+    // Ids of images that reach into the view, with the viewport cell of their top left corner
+    image_anchors: Vec<(u64, usize, isize)>,
+    image_handles: HashMap<u64, PlacedImage>,
+    last_selection_range: Option<SelectionRange>,
+    letter_spacing: f32,
+    // Builtin glyphs on each visible line, as column, character and color
+    line_glyphs: Vec<Vec<(usize, char, cosmic_text::Color)>>,
+    line_prompt_ids: Vec<Option<u64>>,
+    min_contrast: f64,
+    // Adjusted foreground for each foreground and background pair below the minimum contrast
+    min_contrast_cache: HashMap<(cosmic_text::Color, cosmic_text::Color), cosmic_text::Color>,
     mouse_reporter: MouseReporter,
+    notification_last_opt: Option<Instant>,
+    notification_wakeup: bool,
     notifier: Notifier,
+    pid_opt: Option<u32>,
+    search_forwards: bool,
     search_regex_opt: Option<RegexSearch>,
     search_value: String,
     size: Size,
+    tap_state: Arc<Mutex<TapState>>,
+    term_config: Config,
     use_bright_bold: bool,
     zoom_adj: i8,
 }
@@ -197,11 +337,14 @@ impl Terminal {
         profile_id_opt: Option<ProfileId>,
         tab_title_override: Option<String>,
     ) -> Result<Self, io::Error> {
+        let font_family = app_config.font_family(profile_id_opt);
+        let app_config = app_config.profile_config(profile_id_opt);
         let font_stretch = app_config.typed_font_stretch();
         let font_weight = app_config.font_weight;
         let dim_font_weight = app_config.dim_font_weight;
         let bold_font_weight = app_config.bold_font_weight;
         let use_bright_bold = app_config.use_bright_bold;
+        let letter_spacing = f32::from(app_config.letter_spacing);
 
         let metrics = Metrics::new(14.0, 20.0);
 
@@ -214,7 +357,7 @@ impl Terminal {
 
         //TODO: set color to default fg
         let default_attrs = Attrs::new()
-            .family(Family::Monospace)
+            .family(font_family)
             .weight(Weight(font_weight))
             .stretch(font_stretch)
             .color(default_fg)
@@ -229,10 +372,11 @@ impl Terminal {
 
             // Use size of space to determine cell size
             buffer.set_text(font_system, " ", default_attrs, Shaping::Advanced);
+            // Glyphs keep the advance of the font, letter spacing is added by the drawer
             let layout = buffer.line_layout(font_system, 0).unwrap();
             let w = layout[0].w;
             buffer.set_monospace_width(font_system, Some(w));
-            (w, metrics.line_height)
+            ((w + letter_spacing).max(1.0), metrics.line_height)
         };
 
         let size = Size {
@@ -241,37 +385,86 @@ impl Terminal {
             cell_width,
             cell_height,
         };
-        let event_proxy = EventProxy(pane, entity, event_tx);
+        let mut config = config;
+        config.scrolling_history = app_config.scrollback_lines as usize;
+        let tap_state = Arc::new(Mutex::new(TapState {
+            cell_width,
+            cell_height,
+            columns: size.columns(),
+            screen_lines: size.screen_lines(),
+            history_lines: config.scrolling_history,
+            ..Default::default()
+        }));
+        let event_proxy = EventProxy(pane, entity, event_tx, tap_state.clone());
         let term = Arc::new(FairMutex::new(Term::new(
-            config,
+            config.clone(),
             &size,
             event_proxy.clone(),
         )));
 
         let window_id = 0;
         let pty = tty::new(&options, size.into(), window_id)?;
+        #[cfg(unix)]
+        let pid_opt = Some(pty.child().id());
+        #[cfg(not(unix))]
+        let pid_opt = None;
+
+        #[cfg(unix)]
+        let pty = tap::PtyTap::new(pty, term.clone(), tap_state.clone(), event_proxy.clone())?;
 
-        let pty_event_loop = EventLoop::new(term.clone(), event_proxy, pty, options.hold, false)?;
+        let pty_event_loop =
+            EventLoop::new(term.clone(), event_proxy.clone(), pty, options.hold, false)?;
         let notifier = Notifier(pty_event_loop.channel());
         let _pty_join_handle = pty_event_loop.spawn();
 
         Ok(Self {
+            bell_flash_opt: None,
+            bell_last_opt: None,
             bold_font_weight: Weight(bold_font_weight),
             buffer: Arc::new(buffer),
             colors,
             context_menu: None,
+            cursor_blink_interval: Duration::from_millis(750),
+            cursor_blink_start: Instant::now(),
+            cursor_blink_timeout_opt: None,
+            cursor_text_opt: None,
+            damaged_lines: Vec::new(),
             default_attrs,
             dim_font_weight: Weight(dim_font_weight),
+            event_proxy,
+            focused: false,
+            font_fallback_cache: HashMap::new(),
+            font_fallbacks: app_config.font_fallbacks.clone(),
+            font_name: app_config.font_name.clone(),
+            full_update: true,
+            hint_highlight: false,
+            hint_regexes: HintRegexes::new(),
+            hovered_hint: None,
+            hyperlink_tooltip_opt: None,
+            image_anchors: Vec::new(),
+            image_handles: HashMap::new(),
+            last_selection_range: None,
+            letter_spacing,
+            line_glyphs: Vec::new(),
+            line_prompt_ids: Vec::new(),
             metadata_set,
+            min_contrast: app_config.min_contrast(),
+            min_contrast_cache: HashMap::new(),
             mouse_reporter: Default::default(),
             needs_update: true,
+            notification_last_opt: None,
+            notification_wakeup: false,
             notifier,
+            pid_opt,
             profile_id_opt,
+            search_forwards: false,
             search_regex_opt: None,
             search_value: String::new(),
             size,
             tab_title_override,
+            tap_state,
             term,
+            term_config: config,
             use_bright_bold,
             zoom_adj: Default::default(),
         })
@@ -319,6 +512,73 @@ impl Terminal {
         self.with_buffer_mut(|buffer| buffer.set_redraw(redraw));
     }
 
+    /// Whether the cursor is blinking, as set by DECSCUSR or the default style. Blinking
+    /// stops after the configured time without typing
+    pub fn cursor_blinking(&self) -> bool {
+        if let Some(timeout) = self.cursor_blink_timeout_opt {
+            if self.cursor_blink_start.elapsed() >= timeout {
+                return false;
+            }
+        }
+        self.term.lock().cursor_style().blinking
+    }
+
+    /// Time between changes of the blink phase
+    pub fn cursor_blink_interval(&self) -> Duration {
+        self.cursor_blink_interval
+    }
+
+    /// Whether the cursor is shown in the current blink phase
+    pub fn cursor_blink_visible(&self) -> bool {
+        if !self.cursor_blinking() {
+            return true;
+        }
+        let phase = self.cursor_blink_start.elapsed().as_millis()
+            / self.cursor_blink_interval.as_millis().max(1);
+        phase % 2 == 0
+    }
+
+    /// Show the cursor and restart blinking, done when typing
+    pub fn reset_cursor_blink(&mut self) {
+        self.cursor_blink_start = Instant::now();
+        self.needs_update = true;
+    }
+
+    /// Set when the terminal has keyboard focus, unfocused terminals draw a hollow cursor
+    pub fn set_focused(&mut self, focused: bool) {
+        if self.focused != focused {
+            self.focused = focused;
+            // The cursor line is always damaged, so this redraws the cursor
+            self.needs_update = true;
+        }
+    }
+
+    /// Ring the bell, returns false if it was rate limited
+    pub fn bell(&mut self, flash: bool) -> bool {
+        if self
+            .bell_last_opt
+            .is_some_and(|bell_last| bell_last.elapsed() < BELL_RATE_LIMIT)
+        {
+            return false;
+        }
+        let now = Instant::now();
+        self.bell_last_opt = Some(now);
+        if flash {
+            self.bell_flash_opt = Some(now);
+        }
+        true
+    }
+
+    /// Strength of the visual bell, fading from 1 to 0
+    pub fn bell_flash(&mut self) -> Option<f32> {
+        let elapsed = self.bell_flash_opt?.elapsed();
+        if elapsed >= BELL_FLASH_DURATION {
+            self.bell_flash_opt = None;
+            return None;
+        }
+        Some(1.0 - elapsed.as_secs_f32() / BELL_FLASH_DURATION.as_secs_f32())
+    }
+
     pub fn input_no_scroll<I: Into<Cow<'static, [u8]>>>(&self, input: I) {
         self.notifier.notify(input);
     }
@@ -349,6 +609,75 @@ impl Terminal {
         }
     }
 
+    /// Working directory of the shell, if it can be determined
+    pub fn current_dir(&self) -> Option<PathBuf> {
+        let pid = self.pid_opt?;
+        fs::read_link(format!("/proc/{pid}/cwd")).ok()
+    }
+
+    /// Link under the pointer while Ctrl is held
+    pub fn hovered_hint(&self) -> Option<&Hint> {
+        self.hovered_hint.as_ref().filter(|_| self.hint_highlight)
+    }
+
+    /// Tooltip showing the URI of the OSC 8 hyperlink under the pointer
+    pub fn hyperlink_tooltip(&self) -> Option<Weak<Buffer>> {
+        self.hyperlink_tooltip_opt.as_ref().map(Arc::downgrade)
+    }
+
+    /// Update the link under the pointer, `None` clears it. Hyperlinks are always tracked for
+    /// their tooltip, detected links only when `highlight` is set
+    pub fn set_hovered_hint(&mut self, point_opt: Option<Point>, highlight: bool) {
+        if let (Some(point), Some(hint)) = (point_opt, &self.hovered_hint) {
+            if hint.range.contains(&point) && highlight == self.hint_highlight {
+                return;
+            }
+        }
+
+        let hovered_hint = point_opt.and_then(|point| {
+            let term = self.term.lock();
+            hints::hyperlink_at(&term, point).or_else(|| {
+                if highlight {
+                    self.hint_regexes.hint_at(&term, point)
+                } else {
+                    None
+                }
+            })
+        });
+        if hovered_hint == self.hovered_hint && highlight == self.hint_highlight {
+            return;
+        }
+
+        self.hyperlink_tooltip_opt = match &hovered_hint {
+            Some(hint) if hint.kind == HintKind::Hyperlink => {
+                let mut font_system = font_system().write().unwrap();
+                let mut buffer = Buffer::new(font_system.raw(), self.buffer.metrics());
+                buffer.set_wrap(font_system.raw(), Wrap::None);
+                buffer.set_text(
+                    font_system.raw(),
+                    &hint.text,
+                    self.default_attrs,
+                    Shaping::Advanced,
+                );
+                buffer.shape_until_scroll(font_system.raw(), true);
+                Some(Arc::new(buffer))
+            }
+            _ => None,
+        };
+        // Only the lines of the old and new hint change
+        let display_offset = self.term.lock().grid().display_offset() as i32;
+        for hint in self.hovered_hint.iter().chain(hovered_hint.iter()) {
+            for line in hint.range.start().line.0..=hint.range.end().line.0 {
+                if let Ok(line_i) = usize::try_from(line + display_offset) {
+                    self.damaged_lines.push(line_i);
+                }
+            }
+        }
+        self.hovered_hint = hovered_hint;
+        self.hint_highlight = highlight;
+        self.needs_update = true;
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         if width != self.size.width || height != self.size.height {
             let instant = Instant::now();
@@ -358,6 +687,11 @@ impl Terminal {
 
             self.notifier.on_resize(self.size.into());
             self.term.lock().resize(self.size);
+            {
+                let mut tap_state = self.tap_state.lock().unwrap();
+                tap_state.columns = self.size.columns();
+                tap_state.screen_lines = self.size.screen_lines();
+            }
 
             self.with_buffer_mut(|buffer| {
                 let mut font_system = font_system().write().unwrap();
@@ -421,17 +755,28 @@ impl Terminal {
                 }
             }
 
+            self.search_forwards = forwards;
+
             let Some(search_regex) = &mut self.search_regex_opt else {
                 return;
             };
 
-            // Determine search origin
+            // Determine search origin, vi mode searches from its cursor
+            let vi_mode = term.mode().contains(TermMode::VI);
             let grid = term.grid();
             let search_origin = match term
                 .selection
                 .as_ref()
                 .and_then(|selection| selection.to_range(&term))
             {
+                _ if vi_mode => {
+                    let point = term.vi_mode_cursor.point;
+                    if forwards {
+                        point.add(grid, Boundary::Grid, 1)
+                    } else {
+                        point.sub(grid, Boundary::Grid, 1)
+                    }
+                }
                 Some(range) => {
                     //TODO: determine correct search_origin, along with side below
                     if forwards {
@@ -465,6 +810,15 @@ impl Terminal {
                 if forwards { Side::Left } else { Side::Right },
                 None,
             ) {
+                // Move the vi cursor instead of selecting, so visual selections are kept
+                if vi_mode {
+                    term.vi_goto_point(*search_match.start());
+                    drop(term);
+                    self.full_update = true;
+                    self.update();
+                    return;
+                }
+
                 // Scroll to match
                 if forwards {
                     term.scroll_to_point(*search_match.end());
@@ -483,6 +837,17 @@ impl Terminal {
         self.update();
     }
 
+    /// Repeat the last search, in the same direction or reversed, for `n` and `N` in vi mode
+    pub fn search_again(&mut self, reverse: bool) {
+        if !self.search_value.is_empty() {
+            let value = self.search_value.clone();
+            let forwards = self.search_forwards;
+            self.search(&value, forwards != reverse);
+            // Keep the direction of the original search
+            self.search_forwards = forwards;
+        }
+    }
+
     pub fn select_all(&mut self) {
         {
             let mut term = self.term.lock();
@@ -503,14 +868,49 @@ impl Terminal {
         self.update();
     }
 
+    /// Move the end of the current selection to `point`, returns false if there is none
+    pub fn extend_selection(&mut self, point: Point, side: Side) -> bool {
+        let extended = {
+            let mut term = self.term.lock();
+            match &mut term.selection {
+                Some(selection) => {
+                    selection.update(point, side);
+                    true
+                }
+                None => false,
+            }
+        };
+        if extended {
+            self.update();
+        }
+        extended
+    }
+
+    /// Range of the current selection, or `None` if nothing is selected
+    pub fn selection_range(&self) -> Option<SelectionRange> {
+        let term = self.term.lock();
+        term.selection
+            .as_ref()
+            .and_then(|selection| selection.to_range(&term))
+    }
+
     pub fn set_config(
         &mut self,
         config: &AppConfig,
         themes: &HashMap<(String, ColorSchemeKind), Colors>,
     ) {
+        let font_family = config.font_family(self.profile_id_opt);
+        let config = config.profile_config(self.profile_id_opt);
+        let config: &AppConfig = &config;
+
         let mut update_cell_size = false;
         let mut update = false;
         let zoom_adj = self.zoom_adj;
+        if self.default_attrs.family != font_family {
+            self.default_attrs = self.default_attrs.family(font_family);
+            update_cell_size = true;
+        }
+
         if self.default_attrs.stretch != config.typed_font_stretch() {
             self.default_attrs = self.default_attrs.stretch(config.typed_font_stretch());
             update_cell_size = true;
@@ -526,11 +926,29 @@ impl Terminal {
             update_cell_size = true;
         }
 
-        if self.bold_font_weight.0 != config.font_weight {
+        if self.bold_font_weight.0 != config.bold_font_weight {
             self.bold_font_weight = Weight(config.bold_font_weight);
             update_cell_size = true;
         }
 
+        if self.font_name != config.font_name || self.font_fallbacks != config.font_fallbacks {
+            self.font_name.clone_from(&config.font_name);
+            self.font_fallbacks.clone_from(&config.font_fallbacks);
+            self.font_fallback_cache.clear();
+            update = true;
+        }
+
+        if self.min_contrast != config.min_contrast() {
+            self.min_contrast = config.min_contrast();
+            self.min_contrast_cache.clear();
+            update = true;
+        }
+
+        if self.letter_spacing != f32::from(config.letter_spacing) {
+            self.letter_spacing = f32::from(config.letter_spacing);
+            update_cell_size = true;
+        }
+
         if self.use_bright_bold != config.use_bright_bold {
             self.use_bright_bold = config.use_bright_bold;
             update_cell_size = true;
@@ -558,6 +976,38 @@ impl Terminal {
             }
         }
 
+        let cursor_text_opt = config
+            .syntax_theme_cursor_text(self.profile_id_opt)
+            .map(|hex| cosmic_text::Color::rgb(hex.r, hex.g, hex.b));
+        if self.cursor_text_opt != cursor_text_opt {
+            self.cursor_text_opt = cursor_text_opt;
+            update = true;
+        }
+
+        let cursor_style = CursorStyle {
+            shape: match config.cursor_shape {
+                AppCursorShape::Block => CursorShape::Block,
+                AppCursorShape::Underline => CursorShape::Underline,
+                AppCursorShape::Beam => CursorShape::Beam,
+            },
+            blinking: config.cursor_blink,
+        };
+        let scrolling_history = config.scrollback_lines as usize;
+        if self.term_config.default_cursor_style != cursor_style
+            || self.term_config.scrolling_history != scrolling_history
+        {
+            // Programs setting their own style with DECSCUSR keep it
+            self.term_config.default_cursor_style = cursor_style;
+            self.term_config.scrolling_history = scrolling_history;
+            self.term.lock().set_options(self.term_config.clone());
+            self.tap_state.lock().unwrap().history_lines = scrolling_history;
+            update = true;
+        }
+        self.cursor_blink_interval =
+            Duration::from_millis(config.cursor_blink_interval_ms.max(100).into());
+        self.cursor_blink_timeout_opt = (config.cursor_blink_timeout_seconds > 0)
+            .then(|| Duration::from_secs(config.cursor_blink_timeout_seconds.into()));
+
         // NOTE: this is done on every set_config because the changed boolean above does not capture
         // WINDOW_BG changes
         let default_colors_updated = self.update_default_colors(config);
@@ -565,13 +1015,24 @@ impl Terminal {
         if update_cell_size {
             self.update_cell_size();
         } else if update || default_colors_updated {
+            self.full_update = true;
             self.update();
         }
     }
 
     pub fn update_default_colors(&mut self, config: &AppConfig) -> bool {
-        let default_bg = convert_color(&self.colors, Color::Named(NamedColor::Background));
-        let default_fg = convert_color(&self.colors, Color::Named(NamedColor::Foreground));
+        let colors = self.render_colors();
+        let attrs = Attrs::new()
+            .family(self.default_attrs.family)
+            .weight(Weight(config.font_weight))
+            .stretch(config.typed_font_stretch());
+        self.set_default_colors(&colors, attrs)
+    }
+
+    // Use the default colors from `colors`, returns true if they changed
+    fn set_default_colors(&mut self, colors: &Colors, attrs: Attrs<'static>) -> bool {
+        let default_bg = convert_color(colors, Color::Named(NamedColor::Background));
+        let default_fg = convert_color(colors, Color::Named(NamedColor::Foreground));
 
         let new_default_metadata = Metadata::new(default_bg, default_fg);
         let curr_metada_idx = self.default_attrs().metadata;
@@ -579,22 +1040,32 @@ impl Terminal {
         let updated = new_default_metadata != self.metadata_set[curr_metada_idx];
 
         if updated {
+            self.full_update = true;
             self.metadata_set.clear();
             let (default_metadata_idx, _) = self.metadata_set.insert_full(new_default_metadata);
 
-            self.default_attrs = Attrs::new()
-                .family(Family::Monospace)
-                .weight(Weight(config.font_weight))
-                .stretch(config.typed_font_stretch())
-                .color(default_fg)
-                .metadata(default_metadata_idx);
+            self.default_attrs = attrs.color(default_fg).metadata(default_metadata_idx);
         }
 
         updated
     }
 
+    /// Theme colors with the overrides set by the running program, using OSC 4, 10, 11 and 12.
+    /// Overrides are removed again by OSC 104, 110, 111 and 112
+    pub fn render_colors(&self) -> Colors {
+        let term = self.term.lock();
+        let mut colors = self.colors;
+        for index in 0..color::COUNT {
+            if let Some(rgb) = term.colors()[index] {
+                colors[index] = Some(rgb);
+            }
+        }
+        colors
+    }
+
     pub fn update_cell_size(&mut self) {
         let default_attrs = self.default_attrs;
+        let letter_spacing = self.letter_spacing;
         let (cell_width, cell_height) = {
             let mut font_system = font_system().write().unwrap();
             self.with_buffer_mut(|buffer| {
@@ -605,10 +1076,16 @@ impl Terminal {
                 let layout = buffer.line_layout(font_system.raw(), 0).unwrap();
                 let w = layout[0].w;
                 buffer.set_monospace_width(font_system.raw(), Some(w));
-                (w, buffer.metrics().line_height)
+                ((w + letter_spacing).max(1.0), buffer.metrics().line_height)
             })
         };
 
+        {
+            let mut tap_state = self.tap_state.lock().unwrap();
+            tap_state.cell_width = cell_width;
+            tap_state.cell_height = cell_height;
+        }
+
         let old_size = self.size;
         self.size = Size {
             width: 0,
@@ -618,6 +1095,7 @@ impl Terminal {
         };
         self.resize(old_size.width, old_size.height);
 
+        self.full_update = true;
         self.update();
     }
 
@@ -630,68 +1108,108 @@ impl Terminal {
 
         let instant = Instant::now();
 
-        // Only keep default
-        self.metadata_set.truncate(1);
+        // Programs can change the default colors, which are also used outside of the cells
+        let colors = self.render_colors();
+        let cursor_visible = self.cursor_blink_visible();
+        self.set_default_colors(&colors, self.default_attrs);
 
+        let cursor;
+        let display_offset;
         //TODO: is redraw needed after all events?
-        //TODO: use LineDamageBounds
         {
             let buffer = Arc::make_mut(&mut self.buffer);
+            let mut term = self.term.lock();
 
-            let mut line_i = 0;
-            let mut last_point = None;
-            let mut text = String::from(LRI);
+            let screen_lines = term.screen_lines();
+            let selection_range = term
+                .selection
+                .as_ref()
+                .and_then(|selection| selection.to_range(&term));
+
+            // Metadata indexes are kept between updates, so only drop them when every line
+            // is going to be rebuilt anyway
+            let mut full = mem::take(&mut self.full_update)
+                || buffer.lines.len() != screen_lines
+                || selection_range != self.last_selection_range
+                || self.metadata_set.len() > MAX_METADATA;
+            self.last_selection_range = selection_range;
+
+            let mut damaged_lines = Vec::new();
+            match term.damage() {
+                TermDamage::Full => full = true,
+                TermDamage::Partial(damage) => {
+                    damaged_lines.extend(damage.map(|bounds| bounds.line));
+                }
+            }
+            term.reset_damage();
+            damaged_lines.append(&mut self.damaged_lines);
+            damaged_lines.sort_unstable();
+            damaged_lines.dedup();
+
+            if full {
+                // Only keep default
+                self.metadata_set.truncate(1);
+                damaged_lines = (0..screen_lines).collect();
+            }
+
+            if buffer.lines.len() != screen_lines {
+                buffer.lines.resize_with(screen_lines, || {
+                    BufferLine::new(
+                        "",
+                        LineEnding::default(),
+                        AttrsList::new(self.default_attrs),
+                        Shaping::Advanced,
+                    )
+                });
+                buffer.set_redraw(true);
+            }
+
+            self.line_glyphs.resize_with(screen_lines, Vec::new);
+
+            let cursor_shape = term.renderable_content().cursor.shape;
+            // In screen coordinates, for kitty graphics deletions
+            cursor = Point::new(
+                term.grid().cursor.point.line.0.max(0) as usize,
+                term.grid().cursor.point.column,
+            );
+            display_offset = term.grid().display_offset();
+            let grid = term.grid();
+            let display_offset = grid.display_offset() as i32;
+            let mut text = String::new();
             let mut attrs_list = AttrsList::new(self.default_attrs);
-            {
-                let mut term = self.term.lock();
-                //TODO: use damage?
-                match term.damage() {
-                    TermDamage::Full => {}
-                    TermDamage::Partial(_damage_lines) => {}
+            for line_i in damaged_lines {
+                if line_i >= screen_lines {
+                    continue;
                 }
-                term.reset_damage();
-
-                let grid = term.grid();
-                for indexed in grid.display_iter() {
-                    if indexed.point.line != last_point.unwrap_or(indexed.point).line {
-                        while line_i >= buffer.lines.len() {
-                            buffer.lines.push(BufferLine::new(
-                                "",
-                                LineEnding::default(),
-                                AttrsList::new(self.default_attrs),
-                                Shaping::Advanced,
-                            ));
-                            buffer.set_redraw(true);
-                        }
 
-                        if buffer.lines[line_i].set_text(
-                            text.clone(),
-                            LineEnding::default(),
-                            attrs_list.clone(),
-                        ) {
-                            buffer.set_redraw(true);
-                        }
-                        line_i += 1;
+                text.clear();
+                text.push(LRI);
+                attrs_list.clear_spans();
 
-                        text.clear();
-                        text.push(LRI);
-                        attrs_list.clear_spans();
-                    }
-                    //TODO: use indexed.point.column?
+                self.line_glyphs[line_i].clear();
+
+                let line = Line(line_i as i32 - display_offset);
+                for (column, cell) in grid[line].into_iter().enumerate() {
+                    let point = Point::new(line, Column(column));
+
+                    let hyperlink_opt = cell.hyperlink();
 
                     //TODO: skip leading spacer?
-                    if indexed.cell.flags.contains(Flags::WIDE_CHAR_SPACER) {
+                    if cell.flags.contains(Flags::WIDE_CHAR_SPACER) {
                         // Skip wide spacers (cells after wide characters)
                         continue;
                     }
 
                     let start = text.len();
+                    // Builtin glyphs are drawn by the terminal box, leaving a space in the text
+                    let builtin = glyphs::is_builtin(cell.c);
                     // Tab skip/stop is handled by alacritty_terminal
-                    text.push(match indexed.cell.c {
+                    text.push(match cell.c {
                         '\t' => ' ',
+                        _ if builtin => ' ',
                         c => c,
                     });
-                    if let Some(zerowidth) = indexed.cell.zerowidth() {
+                    if let Some(zerowidth) = cell.zerowidth() {
                         for &c in zerowidth {
                             text.push(c);
                         }
@@ -700,45 +1218,46 @@ impl Terminal {
 
                     let mut attrs = self.default_attrs;
 
-                    let cell_fg = if indexed.cell.flags.contains(Flags::DIM) {
-                        as_dim(indexed.cell.fg)
-                    } else if self.use_bright_bold && indexed.cell.flags.contains(Flags::BOLD) {
-                        as_bright(indexed.cell.fg)
+                    let cell_fg = if cell.flags.contains(Flags::DIM) {
+                        as_dim(cell.fg)
+                    } else if self.use_bright_bold && cell.flags.contains(Flags::BOLD) {
+                        as_bright(cell.fg)
                     } else {
-                        indexed.cell.fg
+                        cell.fg
                     };
 
-                    let (mut fg, mut bg) = if indexed.cell.flags.contains(Flags::INVERSE) {
+                    let (mut fg, mut bg) = if cell.flags.contains(Flags::INVERSE) {
                         (
-                            convert_color(&self.colors, indexed.cell.bg),
-                            convert_color(&self.colors, cell_fg),
+                            convert_color(&colors, cell.bg),
+                            convert_color(&colors, cell_fg),
                         )
                     } else {
                         (
-                            convert_color(&self.colors, cell_fg),
-                            convert_color(&self.colors, indexed.cell.bg),
+                            convert_color(&colors, cell_fg),
+                            convert_color(&colors, cell.bg),
                         )
                     };
 
-                    if indexed.cell.flags.contains(Flags::HIDDEN) {
+                    if cell.flags.contains(Flags::HIDDEN) {
                         fg = bg;
                     }
 
-                    // Change color if cursor
-                    if indexed.point == grid.cursor.point
-                        && term.renderable_content().cursor.shape == CursorShape::Block
+                    // Change color if cursor, unfocused terminals draw a hollow one instead
+                    if point == grid.cursor.point
+                        && cursor_shape == CursorShape::Block
+                        && cursor_visible
+                        && self.focused
                     {
-                        //Use specific cursor color if requested
-                        if term.colors()[NamedColor::Cursor].is_some() {
-                            fg = bg;
-                            bg = convert_color(term.colors(), Color::Named(NamedColor::Cursor));
-                        } else if self.colors[NamedColor::Cursor].is_some() {
-                            //Use specific theme cursor color if exists
+                        //Use specific cursor color if requested, or from the theme
+                        if colors[NamedColor::Cursor].is_some() {
                             fg = bg;
-                            bg = convert_color(&self.colors, Color::Named(NamedColor::Cursor));
+                            bg = convert_color(&colors, Color::Named(NamedColor::Cursor));
                         } else {
                             mem::swap(&mut fg, &mut bg);
                         }
+                        if let Some(cursor_text) = self.cursor_text_opt {
+                            fg = cursor_text;
+                        }
                         let fg_rgb = Rgb {
                             r: fg.r(),
                             g: fg.g(),
@@ -751,73 +1270,103 @@ impl Terminal {
                         };
                         let contrast = fg_rgb.contrast(bg_rgb);
                         if contrast < MIN_CURSOR_CONTRAST {
-                            fg = convert_color(&self.colors, Color::Named(NamedColor::Background));
-                            bg = convert_color(&self.colors, Color::Named(NamedColor::Foreground));
+                            fg = convert_color(&colors, Color::Named(NamedColor::Background));
+                            bg = convert_color(&colors, Color::Named(NamedColor::Foreground));
                         }
                     }
 
                     // Change color if selected
-                    if let Some(selection) = &term.selection {
-                        if let Some(range) = selection.to_range(&term) {
-                            if range.contains(indexed.point) {
-                                //TODO: better handling of selection
-                                mem::swap(&mut fg, &mut bg);
-                            }
+                    if let Some(range) = &selection_range {
+                        if range.contains(point) {
+                            //TODO: better handling of selection
+                            mem::swap(&mut fg, &mut bg);
                         }
                     }
 
+                    // Hidden text keeps the color of its background
+                    if self.min_contrast > 1.0 && !cell.flags.contains(Flags::HIDDEN) {
+                        let min_contrast = self.min_contrast;
+                        if self.min_contrast_cache.len() >= MAX_MIN_CONTRAST_CACHE {
+                            self.min_contrast_cache.clear();
+                        }
+                        fg = *self.min_contrast_cache.entry((fg, bg)).or_insert_with(|| {
+                            let rgb = terminal_theme::min_contrast(
+                                Rgb {
+                                    r: fg.r(),
+                                    g: fg.g(),
+                                    b: fg.b(),
+                                },
+                                Rgb {
+                                    r: bg.r(),
+                                    g: bg.g(),
+                                    b: bg.b(),
+                                },
+                                min_contrast,
+                            );
+                            cosmic_text::Color::rgba(rgb.r, rgb.g, rgb.b, fg.a())
+                        });
+                    }
+
+                    if builtin {
+                        self.line_glyphs[line_i].push((column, cell.c, fg));
+                    }
+
                     // Convert foreground to linear
                     attrs = attrs.color(fg);
 
-                    let underline_color = indexed
-                        .cell
+                    let underline_color = cell
                         .underline_color()
-                        .map(|c| convert_color(&self.colors, c))
+                        .map(|c| convert_color(&colors, c))
                         .unwrap_or(fg);
+                    let mut flags = cell.flags;
+                    if let Some(hint) = self.hovered_hint.as_ref().filter(|_| self.hint_highlight) {
+                        if hint.range.contains(&point) {
+                            flags |= Flags::UNDERLINE;
+                        }
+                    }
+
                     let metadata = Metadata::new(bg, fg)
-                        .with_flags(indexed.cell.flags)
+                        .with_flags(flags)
+                        .with_hyperlink(hyperlink_opt)
                         .with_underline_color(underline_color);
                     let (meta_idx, _) = self.metadata_set.insert_full(metadata);
                     attrs = attrs.metadata(meta_idx);
 
                     //TODO: more flags
-                    if indexed.cell.flags.contains(Flags::BOLD) {
+                    if cell.flags.contains(Flags::BOLD) {
                         attrs = attrs.weight(self.bold_font_weight);
-                    } else if indexed.cell.flags.contains(Flags::DIM) {
+                    } else if cell.flags.contains(Flags::DIM) {
                         // if DIM and !BOLD
                         attrs = attrs.weight(self.dim_font_weight);
                     }
-                    if indexed.cell.flags.contains(Flags::ITALIC) {
+                    if cell.flags.contains(Flags::ITALIC) {
                         //TODO: automatically use fake italic
                         attrs = attrs.cache_key_flags(CacheKeyFlags::FAKE_ITALIC);
                     }
+
+                    // Characters missing from the main font use the first fallback font that
+                    // has them
+                    if let Some(fallback) = font_fallback(
+                        &mut self.font_fallback_cache,
+                        &self.font_fallbacks,
+                        attrs,
+                        cell.c,
+                    ) {
+                        attrs = attrs.family(Family::Name(&self.font_fallbacks[fallback]));
+                    }
                     if attrs != attrs_list.defaults() {
                         attrs_list.add_span(start..end, attrs);
                     }
-
-                    last_point = Some(indexed.point);
                 }
-            }
 
-            //TODO: do not repeat!
-            while line_i >= buffer.lines.len() {
-                buffer.lines.push(BufferLine::new(
-                    "",
+                // Only lines with changed text or attributes are reshaped
+                if buffer.lines[line_i].set_text(
+                    text.clone(),
                     LineEnding::default(),
-                    AttrsList::new(self.default_attrs),
-                    Shaping::Advanced,
-                ));
-                buffer.set_redraw(true);
-            }
-
-            if buffer.lines[line_i].set_text(text, LineEnding::default(), attrs_list) {
-                buffer.set_redraw(true);
-            }
-            line_i += 1;
-
-            if buffer.lines.len() != line_i {
-                buffer.lines.truncate(line_i);
-                buffer.set_redraw(true);
+                    attrs_list.clone(),
+                ) {
+                    buffer.set_redraw(true);
+                }
             }
 
             // Shape and trim shape run cache
@@ -826,13 +1375,397 @@ impl Terminal {
                 buffer.shape_until_scroll(font_system.raw(), true);
                 font_system.raw().shape_run_cache.trim(1024);
             }
+
+            // Prompts and images are resolved to lines counted from the top of the scrollback
+            let mut tap_state = self.tap_state.lock().unwrap();
+            tap_state.resolve_anchors(&term);
+            let alt_screen = term.mode().contains(TermMode::ALT_SCREEN);
+            let top = term.grid().history_size() - term.grid().display_offset();
+            self.line_prompt_ids.clear();
+            self.line_prompt_ids.resize(screen_lines, None);
+            if !alt_screen {
+                let first = tap_state
+                    .prompts
+                    .partition_point(|prompt| prompt.start.line < top);
+                for prompt in &tap_state.prompts[first..] {
+                    let Some(line_i) = prompt
+                        .start
+                        .line
+                        .checked_sub(top)
+                        .filter(|line_i| *line_i < screen_lines)
+                    else {
+                        break;
+                    };
+                    self.line_prompt_ids[line_i] = Some(prompt.id);
+                }
+            }
+            self.image_anchors.clear();
+            for (id, image) in tap_state.images.iter() {
+                let Some(anchor) = image
+                    .anchor_opt
+                    .filter(|anchor| anchor.alt_screen == alt_screen)
+                else {
+                    continue;
+                };
+                let line = anchor.point.line as isize - top as isize;
+                let rows = (image.y + image.rows).ceil() as isize;
+                if line < screen_lines as isize && line + rows > 0 {
+                    self.image_anchors.push((*id, anchor.point.column.0, line));
+                }
+            }
         }
 
+        self.update_image_handles(cursor, display_offset);
+
         log::debug!("buffer update {:?}", instant.elapsed());
 
         self.buffer.redraw()
     }
 
+    // Create handles for newly visible images and mark them used, drop handles of images the
+    // tap evicted, and apply kitty graphics deletions that depend on where images are
+    fn update_image_handles(&mut self, cursor: Point<usize>, display_offset: usize) {
+        let mut tap_state = self.tap_state.lock().unwrap();
+        if self.image_handles.is_empty()
+            && tap_state.kitty_deletes.is_empty()
+            && self.image_anchors.is_empty()
+        {
+            return;
+        }
+
+        let now = Instant::now();
+        for &(id, _, _) in &self.image_anchors {
+            let Some(tap_image) = tap_state.images.get_mut(&id) else {
+                continue;
+            };
+            tap_image.last_used = now;
+            if !self.image_handles.contains_key(&id) {
+                self.image_handles.insert(
+                    id,
+                    PlacedImage {
+                        handle: image::Handle::from_pixels(
+                            tap_image.image.width as u32,
+                            tap_image.image.height as u32,
+                            ImagePixels(tap_image.image.clone()),
+                        ),
+                        x: tap_image.x,
+                        y: tap_image.y,
+                        columns: tap_image.columns,
+                        rows: tap_image.rows,
+                        z_index: tap_image.z_index,
+                        kitty: tap_image.kitty_opt.is_some(),
+                    },
+                );
+            }
+        }
+
+        for delete in mem::take(&mut tap_state.kitty_deletes) {
+            // Kitty coordinates are screen cells starting at 1
+            let screen_cell =
+                |x: u32, y: u32| (x as usize).checked_sub(1).zip((y as usize).checked_sub(1));
+            for (id, placed, column, line) in self.placed_images() {
+                if !placed.kitty {
+                    continue;
+                }
+                let columns = column..column + (placed.x + placed.columns).ceil() as usize;
+                let lines = line..line + (placed.y + placed.rows).ceil() as isize;
+                let covers = |x: usize, y: usize| {
+                    columns.contains(&x) && lines.contains(&((y + display_offset) as isize))
+                };
+                let delete = match delete {
+                    KittyDelete::Cursor => covers(cursor.column.0, cursor.line),
+                    KittyDelete::Cell { x, y, z_index } => {
+                        screen_cell(x, y).map_or(false, |(x, y)| covers(x, y))
+                            && z_index.map_or(true, |z_index| z_index == placed.z_index)
+                    }
+                    KittyDelete::Column(x) => {
+                        screen_cell(x, 1).map_or(false, |(x, _)| columns.contains(&x))
+                    }
+                    KittyDelete::Row(y) => screen_cell(1, y).map_or(false, |(_, y)| {
+                        lines.contains(&((y + display_offset) as isize))
+                    }),
+                    _ => false,
+                };
+                if delete {
+                    tap_state.images.remove(&id);
+                }
+            }
+        }
+
+        self.image_handles
+            .retain(|id, _| tap_state.images.contains_key(id));
+    }
+
+    // Images that reach into the view, with the viewport cell of their top left corner
+    fn placed_images(&self) -> Vec<(u64, &PlacedImage, usize, isize)> {
+        self.image_anchors
+            .iter()
+            .filter_map(|&(id, column, line)| {
+                let placed = self.image_handles.get(&id)?;
+                Some((id, placed, column, line))
+            })
+            .collect()
+    }
+
+    /// Images that reach into the view, scaled to the current cell size and sorted by
+    /// z-index
+    pub fn visible_images(&self) -> Vec<VisibleImage> {
+        let mut images: Vec<VisibleImage> = self
+            .placed_images()
+            .into_iter()
+            .map(|(_, placed, column, line)| VisibleImage {
+                handle: placed.handle.clone(),
+                x: (column as f32 + placed.x) * self.size.cell_width,
+                y: (line as f32 + placed.y) * self.size.cell_height,
+                width: placed.columns * self.size.cell_width,
+                height: placed.rows * self.size.cell_height,
+                z_index: placed.z_index,
+            })
+            .collect();
+        images.sort_by_key(|image| image.z_index);
+        images
+    }
+
+    /// Builtin glyphs to draw instead of the font, as line, column, character and color
+    pub fn builtin_glyphs(
+        &self,
+    ) -> impl Iterator<Item = (usize, usize, char, cosmic_text::Color)> + '_ {
+        self.line_glyphs
+            .iter()
+            .enumerate()
+            .flat_map(|(line_i, glyphs)| {
+                glyphs
+                    .iter()
+                    .map(move |&(column, c, color)| (line_i, column, c, color))
+            })
+    }
+
+    pub fn is_vi_mode(&self) -> bool {
+        self.term.lock().mode().contains(TermMode::VI)
+    }
+
+    pub fn toggle_vi_mode(&mut self) {
+        {
+            let mut term = self.term.lock();
+            term.toggle_vi_mode();
+            if !term.mode().contains(TermMode::VI) {
+                term.selection = None;
+                term.scroll_display(TerminalScroll::Bottom);
+            }
+        }
+        // The vi cursor is not covered by terminal damage
+        self.full_update = true;
+        self.update();
+    }
+
+    pub fn vi_motion(&mut self, motion: ViMotion) {
+        self.term.lock().vi_motion(motion);
+        self.full_update = true;
+        self.update();
+    }
+
+    /// Move the vi cursor to the top or bottom of the scrollback
+    pub fn vi_goto_edge(&mut self, top: bool) {
+        {
+            let mut term = self.term.lock();
+            let point = if top {
+                Point::new(term.topmost_line(), Column(0))
+            } else {
+                Point::new(term.bottommost_line(), Column(0))
+            };
+            term.vi_goto_point(point);
+        }
+        self.full_update = true;
+        self.update();
+    }
+
+    /// Start, change or stop a visual selection at the vi cursor
+    pub fn vi_toggle_selection(&mut self, ty: SelectionType) {
+        {
+            let mut term = self.term.lock();
+            match &mut term.selection {
+                Some(selection) if selection.ty == ty && !selection.is_empty() => {
+                    term.selection = None;
+                }
+                Some(selection) if !selection.is_empty() => {
+                    selection.ty = ty;
+                }
+                _ => {
+                    let point = term.vi_mode_cursor.point;
+                    let mut selection = Selection::new(ty, point, Side::Left);
+                    selection.include_all();
+                    term.selection = Some(selection);
+                }
+            }
+        }
+        self.update();
+    }
+
+    /// Copy the visual selection to the clipboard and clear it
+    pub fn vi_yank(&mut self) {
+        let text_opt = {
+            let mut term = self.term.lock();
+            let text_opt = term.selection_to_string();
+            term.selection = None;
+            text_opt
+        };
+        if let Some(text) = text_opt {
+            self.event_proxy
+                .send_event(Event::ClipboardStore(ClipboardType::Clipboard, text));
+        }
+        self.update();
+    }
+
+    /// Scroll the previous or next prompt to the top of the view
+    pub fn jump_to_prompt(&mut self, forwards: bool) {
+        {
+            let mut term = self.term.lock();
+            if term.mode().contains(TermMode::ALT_SCREEN) {
+                return;
+            }
+            let history_size = term.grid().history_size();
+            let display_offset = term.grid().display_offset();
+            // Prompt lines count from the top of the scrollback
+            let top = history_size - display_offset;
+            let line_opt = {
+                let mut tap_state = self.tap_state.lock().unwrap();
+                tap_state.resolve_anchors(&term);
+                let prompts = &tap_state.prompts;
+                let prompt_opt = if forwards {
+                    prompts.get(prompts.partition_point(|prompt| prompt.start.line <= top))
+                } else {
+                    prompts
+                        .partition_point(|prompt| prompt.start.line < top)
+                        .checked_sub(1)
+                        .map(|prompt_i| &prompts[prompt_i])
+                };
+                prompt_opt.map(|prompt| prompt.start.line)
+            };
+            let Some(line) = line_opt else {
+                return;
+            };
+
+            let new_display_offset = history_size.saturating_sub(line);
+            term.scroll_display(TerminalScroll::Delta(
+                new_display_offset as i32 - display_offset as i32,
+            ));
+        }
+        self.update();
+    }
+
+    /// Select the output of the command at the top of the view, or the last command if the
+    /// view is not scrolled
+    pub fn select_command_output(&mut self) {
+        {
+            let mut term = self.term.lock();
+            if term.mode().contains(TermMode::ALT_SCREEN) {
+                return;
+            }
+            let grid = term.grid();
+            let history_size = grid.history_size();
+            // Prompt lines count from the top of the scrollback
+            let to_grid = |point: Point<usize>| {
+                Point::new(Line(point.line as i32 - history_size as i32), point.column)
+            };
+            let reference = if grid.display_offset() > 0 {
+                Point::new(
+                    history_size - grid.display_offset(),
+                    Column(grid.columns() - 1),
+                )
+            } else {
+                Point::new(
+                    history_size + grid.cursor.point.line.0.max(0) as usize,
+                    grid.cursor.point.column,
+                )
+            };
+
+            let range_opt = {
+                let mut tap_state = self.tap_state.lock().unwrap();
+                tap_state.resolve_anchors(&term);
+                let prompts = &tap_state.prompts;
+                // Output runs from its start mark up to the next prompt
+                let output_range = |prompt_i: usize| {
+                    let start = prompts[prompt_i].output_opt?;
+                    let end = match prompts.get(prompt_i + 1) {
+                        Some(next) if next.start <= start => return None,
+                        Some(next) => to_grid(next.start).sub(&*term, Boundary::Grid, 1),
+                        None => term.grid().cursor.point,
+                    };
+                    let start = to_grid(start);
+                    (start <= end).then_some((start, end))
+                };
+
+                // The current prompt has no output yet, so fall back to the one before it
+                let Some(prompt_i) = prompts
+                    .partition_point(|prompt| prompt.start <= reference)
+                    .checked_sub(1)
+                else {
+                    return;
+                };
+                output_range(prompt_i).or_else(|| prompt_i.checked_sub(1).and_then(output_range))
+            };
+            let Some((start, end)) = range_opt else {
+                return;
+            };
+
+            let mut selection = Selection::new(SelectionType::Simple, start, Side::Left);
+            selection.update(end, Side::Right);
+            term.selection = Some(selection);
+        }
+        self.update();
+    }
+
+    /// Take the newest notification requested by the application running in the terminal, at
+    /// most once per rate limit period
+    pub fn take_notification(&mut self) -> Option<Notification> {
+        let mut tap_state = self.tap_state.lock().unwrap();
+        if tap_state.notification_opt.is_none() {
+            return None;
+        }
+        if let Some(notification_last) = self.notification_last_opt {
+            let elapsed = notification_last.elapsed();
+            if elapsed < NOTIFICATION_RATE_LIMIT {
+                // Leave it queued and wake up when the period ends to deliver it
+                if !self.notification_wakeup {
+                    self.notification_wakeup = true;
+                    let event_proxy = self.event_proxy.clone();
+                    let delay = NOTIFICATION_RATE_LIMIT - elapsed;
+                    thread::spawn(move || {
+                        thread::sleep(delay);
+                        event_proxy.send_event(Event::Wakeup);
+                    });
+                }
+                return None;
+            }
+        }
+        self.notification_last_opt = Some(Instant::now());
+        self.notification_wakeup = false;
+        tap_state.notification_opt.take()
+    }
+
+    /// Level of xterm's modifyOtherKeys set by the application, 0 when it is off
+    pub fn modify_other_keys(&self) -> u8 {
+        self.tap_state.lock().unwrap().modify_other_keys
+    }
+
+    /// Take the commands that finished since the last call
+    pub fn take_finished_commands(&self) -> Vec<FinishedCommand> {
+        mem::take(&mut self.tap_state.lock().unwrap().finished_commands)
+    }
+
+    /// Visible lines holding the prompt of a command that failed
+    pub fn failed_prompt_lines(&self) -> Vec<usize> {
+        let tap_state = self.tap_state.lock().unwrap();
+        self.line_prompt_ids
+            .iter()
+            .enumerate()
+            .filter_map(|(line_i, id_opt)| {
+                let status = tap_state.exit_statuses.get(&(*id_opt)?)?;
+                (*status != 0).then_some(line_i)
+            })
+            .collect()
+    }
+
     pub fn viewport_to_point(&self, point: Point<usize>) -> Point {
         let term = self.term.lock();
         viewport_to_point(term.grid().display_offset(), point)
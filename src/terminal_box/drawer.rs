@@ -1,5 +1,5 @@
 use alacritty_terminal::{
-    term::cell::Flags,
+    term::{cell::Flags, TermMode},
     vte::ansi::{CursorShape, NamedColor},
 };
 
@@ -11,6 +11,7 @@ use cosmic::{
         Color, Point, Rectangle, Size, Vector,
     },
     iced_core::{
+        image::{self, FilterMethod},
         layout::Layout,
         renderer::{self, Quad, Renderer as _},
         text::Renderer as _,
@@ -24,12 +25,15 @@ use indexmap::IndexSet;
 use std::{array, cmp, time::Instant};
 
 use crate::{
-    terminal::metadata::Metadata,
+    terminal::{glyphs, metadata::Metadata, terminal::VisibleImage},
     terminal_box::{enums::Dragging, state::State},
 };
 
 use super::terminal_box::TerminalBox;
 
+// Rendered builtin glyphs kept before the cache is cleared
+const MAX_GLYPH_HANDLES: usize = 1024;
+
 pub(super) fn draw<'a, Message>(
     terminal_box: &TerminalBox<'a, Message>,
     tree: &tree::Tree,
@@ -65,6 +69,9 @@ pub(super) fn draw<'a, Message>(
     // Ensure terminal is the right size
     terminal.resize(view_w as u32, view_h as u32);
 
+    // Block cursors are part of the cell colors, unless hollow
+    terminal.set_focused(state.is_focused);
+
     // Update if needed
     if terminal.needs_update {
         terminal.update();
@@ -94,6 +101,13 @@ pub(super) fn draw<'a, Message>(
         );
     }
 
+    // Letter spacing is added to the font advance of each column instead of scaling the glyphs
+    let cell_width = terminal.size().cell_width;
+    let advance = terminal
+        .with_buffer(|buffer| buffer.monospace_width())
+        .unwrap_or(cell_width);
+    let spacing = cell_width - advance;
+
     // Render cell backgrounds that do not match default
     terminal.with_buffer(|buffer| {
         for run in buffer.layout_runs() {
@@ -101,6 +115,8 @@ pub(super) fn draw<'a, Message>(
                 default_metadata: usize,
                 metadata: usize,
                 glyph_font_size: f32,
+                // Glyphs are laid out a font advance apart, cells include the letter spacing
+                scale_x: f32,
                 start_x: f32,
                 end_x: f32,
                 line_height: f32,
@@ -117,13 +133,13 @@ pub(super) fn draw<'a, Message>(
                     is_focused: bool,
                 ) {
                     if glyph.metadata == self.metadata {
-                        self.end_x = glyph.x + glyph.w;
+                        self.end_x = (glyph.x + glyph.w) * self.scale_x;
                     } else {
                         self.fill(renderer, is_focused);
                         self.metadata = glyph.metadata;
                         self.glyph_font_size = glyph.font_size;
-                        self.start_x = glyph.x;
-                        self.end_x = glyph.x + glyph.w;
+                        self.start_x = glyph.x * self.scale_x;
+                        self.end_x = (glyph.x + glyph.w) * self.scale_x;
                     }
                 }
 
@@ -174,7 +190,7 @@ pub(super) fn draw<'a, Message>(
                         );
                     }
 
-                    if !metadata.flags.is_empty() {
+                    if !metadata.flags.is_empty() || metadata.hyperlink.is_some() {
                         let style_line_height = (self.glyph_font_size / 10.0).clamp(2.0, 16.0);
 
                         let line_color = cosmic_text_to_iced_color(metadata.underline_color);
@@ -257,6 +273,16 @@ pub(super) fn draw<'a, Message>(
                             draw_repeated(&[dot, gap]);
                         }
 
+                        // OSC 8 hyperlinks without their own underline style get a dotted one
+                        if metadata.hyperlink.is_some()
+                            && !metadata.flags.intersects(Flags::ALL_UNDERLINES)
+                        {
+                            let bottom_offset = style_line_height * 2.0;
+                            let dot = (2.0, Some(bottom_offset));
+                            let gap = (2.0, None);
+                            draw_repeated(&[dot, gap]);
+                        }
+
                         if metadata.flags.contains(Flags::DASHED_UNDERLINE) {
                             let bottom_offset = style_line_height * 2.0;
                             let dash = (6.0, Some(bottom_offset));
@@ -286,6 +312,7 @@ pub(super) fn draw<'a, Message>(
                 default_metadata,
                 metadata: default_metadata,
                 glyph_font_size: 0.0,
+                scale_x: cell_width / advance,
                 start_x: 0.0,
                 end_x: 0.0,
                 line_height: buffer.metrics().line_height,
@@ -300,12 +327,148 @@ pub(super) fn draw<'a, Message>(
         }
     });
 
-    renderer.fill_raw(Raw {
-        buffer: terminal.buffer_weak(),
-        position: view_position,
-        color: Color::new(1.0, 1.0, 1.0, 1.0), // TODO
-        clip_bounds: Rectangle::new(view_position, Size::new(view_w as f32, view_h as f32)),
-    });
+    let view_bounds = Rectangle::new(view_position, Size::new(view_w as f32, view_h as f32));
+    let buffer_weak = terminal.buffer_weak();
+    let columns = terminal.size().columns();
+    // With letter spacing, each column is drawn separately, offset to the middle of its cell and
+    // clipped to it. Wide glyphs are split at the cell boundary
+    let draw_text = |renderer: &mut cosmic::iced::Renderer| {
+        let text = |position: Point, clip_bounds: Rectangle| Raw {
+            buffer: buffer_weak.clone(),
+            position,
+            color: Color::new(1.0, 1.0, 1.0, 1.0), // TODO
+            clip_bounds,
+        };
+        if spacing == 0.0 {
+            renderer.fill_raw(text(view_position, view_bounds));
+            return;
+        }
+        for column in 0..columns {
+            let cell_bounds = Rectangle::new(
+                view_position + Vector::new(column as f32 * cell_width, 0.0),
+                Size::new(cell_width, view_h as f32),
+            );
+            if let Some(clip_bounds) = cell_bounds.intersection(&view_bounds) {
+                let offset = Vector::new(column as f32 * spacing + spacing / 2.0, 0.0);
+                renderer.fill_raw(text(view_position + offset, clip_bounds));
+            }
+        }
+    };
+
+    // Render images with a negative z-index between the cell backgrounds and the text, and the
+    // rest above the text. Layers are drawn after the primitives of their parent, so the text
+    // has to go in the same layer as the images below it to stay on top
+    let images = terminal.visible_images();
+    let draw_image = |renderer: &mut cosmic::iced::Renderer, visible: &VisibleImage| {
+        image::Renderer::draw(
+            renderer,
+            visible.handle.clone(),
+            FilterMethod::Linear,
+            Rectangle::new(
+                view_position + Vector::new(visible.x, visible.y),
+                Size::new(visible.width, visible.height),
+            ),
+        );
+    };
+    // Box drawing, block and Powerline glyphs are drawn to the exact cell bounds instead of
+    // the font, between the images below the text and the text. Glyphs made of rectangles are
+    // filled directly, so only antialiased ones are rendered and cached per color
+    let mut glyph_quads: Vec<(Rectangle, Color)> = Vec::new();
+    let mut glyph_images: Vec<(image::Handle, Rectangle)> = Vec::new();
+    {
+        let cell_height = terminal.size().cell_height;
+        let mut glyph_handles = state.glyph_handles.borrow_mut();
+        if glyph_handles.len() > MAX_GLYPH_HANDLES {
+            glyph_handles.clear();
+        }
+        for (line_i, column, c, color) in terminal.builtin_glyphs() {
+            let x = (column as f32 * cell_width).floor();
+            let y = (line_i as f32 * cell_height).floor();
+            let width = ((column + 1) as f32 * cell_width).floor() - x;
+            let height = ((line_i + 1) as f32 * cell_height).floor() - y;
+            if width < 1.0 || height < 1.0 {
+                continue;
+            }
+            let position = view_position + Vector::new(x, y);
+            if let Some(rects) = glyphs::rectangles(c, width as usize, height as usize) {
+                for rect in rects {
+                    glyph_quads.push((
+                        Rectangle::new(
+                            position + Vector::new(rect.x as f32, rect.y as f32),
+                            Size::new(rect.width as f32, rect.height as f32),
+                        ),
+                        Color::from_rgba8(
+                            color.r(),
+                            color.g(),
+                            color.b(),
+                            f32::from(color.a()) * f32::from(rect.alpha) / (255.0 * 255.0),
+                        ),
+                    ));
+                }
+                continue;
+            }
+            let key = (c, width as usize, height as usize, color.0);
+            let handle = match glyph_handles.get(&key) {
+                Some(handle) => handle.clone(),
+                None => {
+                    let Some(alpha) = glyphs::rasterize(c, key.1, key.2) else {
+                        continue;
+                    };
+                    let mut rgba = Vec::with_capacity(alpha.len() * 4);
+                    for a in alpha {
+                        rgba.extend_from_slice(&[
+                            color.r(),
+                            color.g(),
+                            color.b(),
+                            (u16::from(a) * u16::from(color.a()) / 255) as u8,
+                        ]);
+                    }
+                    let handle = image::Handle::from_pixels(key.1 as u32, key.2 as u32, rgba);
+                    glyph_handles.insert(key, handle.clone());
+                    handle
+                }
+            };
+            glyph_images.push((handle, Rectangle::new(position, Size::new(width, height))));
+        }
+    }
+    let draw_glyphs = |renderer: &mut cosmic::iced::Renderer| {
+        for (bounds, color) in &glyph_quads {
+            renderer.fill_quad(
+                Quad {
+                    bounds: *bounds,
+                    ..Default::default()
+                },
+                *color,
+            );
+        }
+        for (handle, bounds) in &glyph_images {
+            image::Renderer::draw(renderer, handle.clone(), FilterMethod::Nearest, *bounds);
+        }
+    };
+
+    let (below, above): (Vec<_>, Vec<_>) = images.iter().partition(|image| image.z_index < 0);
+    if below.is_empty() {
+        draw_glyphs(renderer);
+        draw_text(renderer);
+    } else {
+        renderer.with_layer(view_bounds, |renderer| {
+            for visible in below {
+                draw_image(renderer, visible);
+            }
+            // Quads of a layer are drawn before its images
+            renderer.with_layer(view_bounds, |renderer| {
+                draw_glyphs(renderer);
+                draw_text(renderer);
+            });
+        });
+    }
+    if !above.is_empty() {
+        renderer.with_layer(view_bounds, |renderer| {
+            for visible in above {
+                draw_image(renderer, visible);
+            }
+        });
+    }
 
     // Draw scrollbar
     if let Some((start, end)) = terminal.scrollbar() {
@@ -381,20 +544,51 @@ pub(super) fn draw<'a, Message>(
         state.scrollbar_rect.set(Rectangle::default())
     }
 
-    // Draw cursor
+    // Mark prompts of failed commands in the left padding
     {
+        let failed_lines = terminal.failed_prompt_lines();
+        if !failed_lines.is_empty() {
+            let color = terminal.render_colors()[NamedColor::Red]
+                .map(|rgb| Color::from_rgb8(rgb.r, rgb.g, rgb.b))
+                .unwrap_or(Color::from_rgb8(0xcc, 0x00, 0x00));
+            let width = terminal_box.padding.left.max(2.0);
+            let height = terminal.size().cell_height;
+            for line_i in failed_lines {
+                let quad = Quad {
+                    bounds: Rectangle::new(
+                        layout.position()
+                            + Vector::new(
+                                0.0,
+                                terminal_box.padding.top + (line_i as f32 * height).floor(),
+                            ),
+                        Size::new(width, height),
+                    ),
+                    ..Default::default()
+                };
+                renderer.fill_quad(quad, color);
+            }
+        }
+    }
+
+    // Draw cursor, unless it is blinking and in its hidden phase. Unfocused terminals do not
+    // blink and always show a hollow block
+    if !state.is_focused || terminal.cursor_blink_visible() {
         let cursor = terminal.term.lock().renderable_content().cursor;
         let col = cursor.point.column.0;
         let line = cursor.point.line.0;
-        let color = terminal.term.lock().colors()[NamedColor::Cursor]
-            .or(terminal.colors()[NamedColor::Cursor])
+        let color = terminal.render_colors()[NamedColor::Cursor]
             .map(|rgb| Color::from_rgb8(rgb.r, rgb.g, rgb.b))
             .unwrap_or(Color::WHITE); // TODO default color from theme?
         let width = terminal.size().cell_width;
         let height = terminal.size().cell_height;
         let top_left = view_position
             + Vector::new((col as f32 * width).floor(), (line as f32 * height).floor());
-        match cursor.shape {
+        let shape = match cursor.shape {
+            CursorShape::Hidden => CursorShape::Hidden,
+            _ if !state.is_focused => CursorShape::HollowBlock,
+            shape => shape,
+        };
+        match shape {
             CursorShape::Beam => {
                 let quad = Quad {
                     bounds: Rectangle::new(top_left, Size::new(1.0, height)),
@@ -416,11 +610,128 @@ pub(super) fn draw<'a, Message>(
                 };
                 renderer.fill_quad(quad, color);
             }
-            CursorShape::HollowBlock => {} // TODO not sure when this would even be activated
+            CursorShape::HollowBlock => {
+                let quad = Quad {
+                    bounds: Rectangle::new(top_left, Size::new(width, height)),
+                    border: Border {
+                        radius: 0.0.into(),
+                        width: 1.0,
+                        color,
+                    },
+                    ..Default::default()
+                };
+                renderer.fill_quad(quad, Color::TRANSPARENT);
+            }
             CursorShape::Block | CursorShape::Hidden => {} // Block is handled seperately
         }
     }
 
+    // Draw vi cursor as an outline, so the terminal cursor stays visible underneath
+    {
+        let vi_point_opt = {
+            let term = terminal.term.lock();
+            term.mode().contains(TermMode::VI).then(|| {
+                let point = term.vi_mode_cursor.point;
+                (
+                    point.column.0,
+                    point.line.0 + term.grid().display_offset() as i32,
+                )
+            })
+        };
+        if let Some((col, line)) = vi_point_opt {
+            let color = terminal.render_colors()[NamedColor::Cursor]
+                .map(|rgb| Color::from_rgb8(rgb.r, rgb.g, rgb.b))
+                .unwrap_or(Color::WHITE);
+            let width = terminal.size().cell_width;
+            let height = terminal.size().cell_height;
+            let top_left = view_position
+                + Vector::new((col as f32 * width).floor(), (line as f32 * height).floor());
+            let quad = Quad {
+                bounds: Rectangle::new(top_left, Size::new(width, height)),
+                border: Border {
+                    radius: 0.0.into(),
+                    width: 1.0,
+                    color,
+                },
+                ..Default::default()
+            };
+            renderer.fill_quad(quad, Color::TRANSPARENT);
+        }
+    }
+
+    // Draw visual bell as a fading overlay in the foreground color
+    if let Some(strength) = terminal.bell_flash() {
+        let meta = &terminal.metadata_set[terminal.default_attrs().metadata];
+        let mut color = Color::from_rgb8(meta.fg.r(), meta.fg.g(), meta.fg.b());
+        color.a = 0.3 * strength;
+        renderer.fill_quad(
+            Quad {
+                bounds: layout.bounds(),
+                border: terminal_box.border,
+                ..Default::default()
+            },
+            color,
+        );
+    }
+
+    // Draw hyperlink tooltip next to the pointer
+    if let (Some(tooltip), Some(p)) = (
+        terminal.hyperlink_tooltip(),
+        cursor_position.position_in(layout.bounds()),
+    ) {
+        let tooltip_size = tooltip.upgrade().map(|buffer| {
+            let mut w: f32 = 0.0;
+            let mut h: f32 = 0.0;
+            for run in buffer.layout_runs() {
+                w = w.max(run.line_w);
+                h += buffer.metrics().line_height;
+            }
+            Size::new(w, h)
+        });
+        if let Some(tooltip_size) = tooltip_size {
+            let meta = &terminal.metadata_set[terminal.default_attrs().metadata];
+            let padding = f32::from(cosmic_theme.spacing.space_xxs);
+            let bounds = layout.bounds();
+            let size = Size::new(
+                tooltip_size.width + padding * 2.0,
+                tooltip_size.height + padding * 2.0,
+            );
+            // Below the pointer, kept inside the terminal
+            let x = (bounds.x + p.x).min(bounds.x + bounds.width - size.width);
+            let mut y = bounds.y + p.y + terminal.size().cell_height;
+            if y + size.height > bounds.y + bounds.height {
+                y = bounds.y + p.y - terminal.size().cell_height - size.height;
+            }
+            let position = Point::new(x.max(bounds.x), y.max(bounds.y));
+
+            // In its own layer so it covers the text and images
+            renderer.with_layer(bounds, |renderer| {
+                renderer.fill_quad(
+                    Quad {
+                        bounds: Rectangle::new(position, size),
+                        border: Border {
+                            radius: padding.into(),
+                            width: 1.0,
+                            color: Color::from_rgb8(
+                                meta.underline_color.r(),
+                                meta.underline_color.g(),
+                                meta.underline_color.b(),
+                            ),
+                        },
+                        ..Default::default()
+                    },
+                    Color::from_rgb8(meta.bg.r(), meta.bg.g(), meta.bg.b()),
+                );
+                renderer.fill_raw(Raw {
+                    buffer: tooltip,
+                    position: position + Vector::new(padding, padding),
+                    color: Color::new(1.0, 1.0, 1.0, 1.0),
+                    clip_bounds: bounds,
+                });
+            });
+        }
+    }
+
     let duration = instant.elapsed();
     log::trace!("redraw {}, {}: {:?}", view_w, view_h, duration);
 }
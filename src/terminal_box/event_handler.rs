@@ -4,24 +4,30 @@ use alacritty_terminal::{
     index::{Column as TermColumn, Point as TermPoint, Side as TermSide},
     selection::{Selection, SelectionType},
     term::TermMode,
+    vi_mode::ViMotion,
 };
 
 use cosmic::{
     iced::{
         event::{Event, Status},
-        keyboard::{Event as KeyEvent, Key},
+        keyboard::{Event as KeyEvent, Key, Modifiers},
         mouse::{self, Button, Event as MouseEvent, ScrollDelta},
-        Point, Rectangle,
+        Padding, Point, Rectangle,
+    },
+    iced_core::{
+        clipboard::Clipboard, keyboard::key::Named, layout::Layout, widget::tree,
+        window::RedrawRequest, Shell,
     },
-    iced_core::{clipboard::Clipboard, keyboard::key::Named, layout::Layout, widget::tree, Shell},
 };
 
 use std::time::Instant;
 
-use crate::TerminalScroll;
+use crate::{Terminal, TerminalScroll};
 
 use super::{
     enums::{ClickKind, Dragging},
+    kitty_keyboard::{self, KeyEventKind},
+    modify_other_keys,
     state::State,
     terminal_box::TerminalBox,
 };
@@ -45,10 +51,196 @@ where
     let mut terminal = terminal_box.terminal.lock().unwrap();
     let buffer_size = terminal.with_buffer(|buffer| buffer.size());
 
-    let is_app_cursor = terminal.term.lock().mode().contains(TermMode::APP_CURSOR);
-    let is_mouse_mode = terminal.term.lock().mode().intersects(TermMode::MOUSE_MODE);
+    let term_mode = *terminal.term.lock().mode();
+    let is_app_cursor = term_mode.contains(TermMode::APP_CURSOR);
+    let is_mouse_mode = term_mode.intersects(TermMode::MOUSE_MODE);
+    // Shift selects text even when the program asked for mouse events, and a selection started
+    // that way keeps going until the button is released
+    let is_mouse_reporting = is_mouse_mode && !state.modifiers.shift() && state.dragging.is_none();
+    let is_vi_mode = term_mode.contains(TermMode::VI);
+
+    // Keep drawing while the visual bell fades out
+    if terminal.bell_flash().is_some() {
+        shell.request_redraw(RedrawRequest::NextFrame);
+    }
+
+    // Typing shows the cursor and restarts blinking
+    if state.is_focused && matches!(event, Event::Keyboard(KeyEvent::KeyPressed { .. })) {
+        terminal.reset_cursor_blink();
+    }
+
+    // Keys pressed with other flags are not released with the new ones, including when the
+    // program turns the protocol off while a key is held
+    let kitty_keyboard_mode = term_mode & TermMode::KITTY_KEYBOARD_PROTOCOL;
+    if state.kitty_keyboard_mode != kitty_keyboard_mode {
+        state.kitty_keyboard_mode = kitty_keyboard_mode;
+        state.pressed_keys.clear();
+    }
+
+    // Programs that enabled the kitty keyboard protocol get keys in its encoding
+    if state.is_focused && !is_vi_mode && term_mode.intersects(TermMode::KITTY_KEYBOARD_PROTOCOL) {
+        let key_event_opt = match &event {
+            Event::Keyboard(KeyEvent::KeyPressed {
+                key,
+                location,
+                modifiers,
+                text,
+            }) => {
+                let bound = terminal_box
+                    .key_binds
+                    .keys()
+                    .any(|key_bind| key_bind.matches(*modifiers, key));
+                // Shift with these keys scrolls the terminal
+                let scroll = *modifiers == Modifiers::SHIFT
+                    && matches!(
+                        key,
+                        Key::Named(Named::PageUp | Named::PageDown | Named::Home | Named::End)
+                    );
+                if bound || scroll {
+                    None
+                } else {
+                    // A key pressed again before it is released is repeating
+                    let kind = if state.pressed_keys.insert(kitty_keyboard::pressed_key(key)) {
+                        KeyEventKind::Press
+                    } else {
+                        KeyEventKind::Repeat
+                    };
+                    Some((key, *location, *modifiers, text.as_deref(), kind))
+                }
+            }
+            // Only keys that were sent are released
+            Event::Keyboard(KeyEvent::KeyReleased {
+                key,
+                location,
+                modifiers,
+            }) if state.pressed_keys.remove(&kitty_keyboard::pressed_key(key)) => {
+                Some((key, *location, *modifiers, None, KeyEventKind::Release))
+            }
+            _ => None,
+        };
+        if let Some((key, location, modifiers, text, kind)) = key_event_opt {
+            if let Some(code) =
+                kitty_keyboard::encode(term_mode, key, location, modifiers, text, kind)
+            {
+                if !code.is_empty() {
+                    terminal.input_scroll(code);
+                }
+                return Status::Captured;
+            }
+        }
+    }
+
+    // With xterm's modifyOtherKeys, keys the legacy encoding would lose get their own codes
+    if let Event::Keyboard(KeyEvent::KeyPressed {
+        key,
+        modifiers,
+        text,
+        ..
+    }) = &event
+    {
+        let level = terminal.modify_other_keys();
+        if state.is_focused
+            && !is_vi_mode
+            && level > 0
+            && !terminal_box
+                .key_binds
+                .keys()
+                .any(|key_bind| key_bind.matches(*modifiers, key))
+        {
+            if let Some(code) = modify_other_keys::encode(level, key, *modifiers, text.as_deref()) {
+                terminal.input_scroll(code);
+                return Status::Captured;
+            }
+        }
+    }
+
     let mut status = Status::Ignored;
     match event {
+        Event::Keyboard(KeyEvent::KeyPressed { key, modifiers, .. })
+            if state.is_focused && is_vi_mode =>
+        {
+            for key_bind in terminal_box.key_binds.keys() {
+                if key_bind.matches(modifiers, &key) {
+                    return Status::Captured;
+                }
+            }
+
+            // Keys move the vi cursor and never reach the PTY while vi mode is on
+            let motion_opt = match &key {
+                Key::Named(Named::ArrowLeft) => Some(ViMotion::Left),
+                Key::Named(Named::ArrowDown) => Some(ViMotion::Down),
+                Key::Named(Named::ArrowUp) => Some(ViMotion::Up),
+                Key::Named(Named::ArrowRight) => Some(ViMotion::Right),
+                Key::Named(Named::Home) => Some(ViMotion::First),
+                Key::Named(Named::End) => Some(ViMotion::Last),
+                Key::Character(c) if !modifiers.control() => match c.as_str() {
+                    "h" => Some(ViMotion::Left),
+                    "j" => Some(ViMotion::Down),
+                    "k" => Some(ViMotion::Up),
+                    "l" => Some(ViMotion::Right),
+                    "w" => Some(ViMotion::SemanticRight),
+                    "b" => Some(ViMotion::SemanticLeft),
+                    "e" => Some(ViMotion::SemanticRightEnd),
+                    "W" => Some(ViMotion::WordRight),
+                    "B" => Some(ViMotion::WordLeft),
+                    "E" => Some(ViMotion::WordRightEnd),
+                    "0" => Some(ViMotion::First),
+                    "$" => Some(ViMotion::Last),
+                    "^" => Some(ViMotion::FirstOccupied),
+                    "H" => Some(ViMotion::High),
+                    "M" => Some(ViMotion::Middle),
+                    "L" => Some(ViMotion::Low),
+                    "%" => Some(ViMotion::Bracket),
+                    "{" => Some(ViMotion::ParagraphUp),
+                    "}" => Some(ViMotion::ParagraphDown),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(motion) = motion_opt {
+                terminal.vi_motion(motion);
+                return Status::Captured;
+            }
+
+            match &key {
+                Key::Named(Named::Escape) => {
+                    let had_selection = {
+                        let mut term = terminal.term.lock();
+                        term.selection.take().is_some()
+                    };
+                    if had_selection {
+                        terminal.update();
+                    } else {
+                        terminal.toggle_vi_mode();
+                    }
+                }
+                Key::Named(Named::PageUp) => terminal.scroll(TerminalScroll::PageUp),
+                Key::Named(Named::PageDown) => terminal.scroll(TerminalScroll::PageDown),
+                Key::Character(c) if modifiers.control() => {
+                    if c.as_str() == "v" {
+                        terminal.vi_toggle_selection(SelectionType::Block);
+                    }
+                }
+                Key::Character(c) => match c.as_str() {
+                    "i" => terminal.toggle_vi_mode(),
+                    "g" => terminal.vi_goto_edge(true),
+                    "G" => terminal.vi_goto_edge(false),
+                    "v" => terminal.vi_toggle_selection(SelectionType::Simple),
+                    "V" => terminal.vi_toggle_selection(SelectionType::Lines),
+                    "y" => terminal.vi_yank(),
+                    "n" => terminal.search_again(false),
+                    "N" => terminal.search_again(true),
+                    "/" | "?" => {
+                        if let Some(on_vi_search) = &terminal_box.on_vi_search {
+                            shell.publish(on_vi_search(c.as_str() == "/"));
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+            return Status::Captured;
+        }
         Event::Keyboard(KeyEvent::KeyPressed {
             key: Key::Named(named),
             modifiers,
@@ -188,6 +380,16 @@ where
         }
         Event::Keyboard(KeyEvent::ModifiersChanged(modifiers)) => {
             state.modifiers = modifiers;
+
+            // Links are only highlighted while Ctrl is held
+            let point_opt = if state.dragging.is_none() {
+                cursor_position
+                    .position_in(layout.bounds())
+                    .and_then(|p| hint_point(&terminal, terminal_box.padding, buffer_size, p))
+            } else {
+                None
+            };
+            terminal.set_hovered_hint(point_opt, modifiers.control());
         }
         Event::Keyboard(KeyEvent::KeyPressed {
             text,
@@ -267,6 +469,18 @@ where
             }
         }
         Event::Mouse(MouseEvent::ButtonPressed(button)) => {
+            // Ctrl+click opens the hovered link, even in mouse mode
+            if button == Button::Left && state.modifiers.control() {
+                if let (Some(hint), Some(on_open_url)) =
+                    (terminal.hovered_hint(), &terminal_box.on_open_url)
+                {
+                    let url = hint.url(terminal.current_dir().as_deref());
+                    shell.publish(on_open_url(url));
+                    state.is_focused = true;
+                    return Status::Captured;
+                }
+            }
+
             if let Some(p) = cursor_position.position_in(layout.bounds()) {
                 let x = p.x - terminal_box.padding.left;
                 let y = p.y - terminal_box.padding.top;
@@ -274,7 +488,7 @@ where
                 let col = x / terminal.size().cell_width;
                 let row = y / terminal.size().cell_height;
 
-                if is_mouse_mode {
+                if is_mouse_reporting {
                     terminal.report_mouse(event, &state.modifiers, col as u32, row as u32);
                 } else {
                     state.is_focused = true;
@@ -289,20 +503,6 @@ where
                             && y >= 0.0
                             && y < buffer_size.1.unwrap_or(0.0)
                         {
-                            let click_kind =
-                                if let Some((click_kind, click_time)) = state.click.take() {
-                                    if click_time.elapsed() < terminal_box.click_timing {
-                                        match click_kind {
-                                            ClickKind::Single => ClickKind::Double,
-                                            ClickKind::Double => ClickKind::Triple,
-                                            ClickKind::Triple => ClickKind::Single,
-                                        }
-                                    } else {
-                                        ClickKind::Single
-                                    }
-                                } else {
-                                    ClickKind::Single
-                                };
                             let location = terminal.viewport_to_point(TermPoint::new(
                                 row as usize,
                                 TermColumn(col as usize),
@@ -312,23 +512,47 @@ where
                             } else {
                                 TermSide::Right
                             };
-                            let selection = match click_kind {
-                                ClickKind::Single => {
-                                    Selection::new(SelectionType::Simple, location, side)
-                                }
-                                ClickKind::Double => {
-                                    Selection::new(SelectionType::Semantic, location, side)
-                                }
-                                ClickKind::Triple => {
-                                    Selection::new(SelectionType::Lines, location, side)
-                                }
-                            };
+                            // Shift+click moves the end of the selection
+                            if state.modifiers.shift() && terminal.extend_selection(location, side)
                             {
-                                let mut term = terminal.term.lock();
-                                term.selection = Some(selection);
+                                state.click = None;
+                            } else {
+                                let click_kind =
+                                    if let Some((click_kind, click_time)) = state.click.take() {
+                                        if click_time.elapsed() < terminal_box.click_timing {
+                                            match click_kind {
+                                                ClickKind::Single => ClickKind::Double,
+                                                ClickKind::Double => ClickKind::Triple,
+                                                ClickKind::Triple => ClickKind::Single,
+                                            }
+                                        } else {
+                                            ClickKind::Single
+                                        }
+                                    } else {
+                                        ClickKind::Single
+                                    };
+                                let selection = match click_kind {
+                                    // Alt or Ctrl+Alt selects a block of columns
+                                    ClickKind::Single if state.modifiers.alt() => {
+                                        Selection::new(SelectionType::Block, location, side)
+                                    }
+                                    ClickKind::Single => {
+                                        Selection::new(SelectionType::Simple, location, side)
+                                    }
+                                    ClickKind::Double => {
+                                        Selection::new(SelectionType::Semantic, location, side)
+                                    }
+                                    ClickKind::Triple => {
+                                        Selection::new(SelectionType::Lines, location, side)
+                                    }
+                                };
+                                {
+                                    let mut term = terminal.term.lock();
+                                    term.selection = Some(selection);
+                                }
+                                terminal.needs_update = true;
+                                state.click = Some((click_kind, Instant::now()));
                             }
-                            terminal.needs_update = true;
-                            state.click = Some((click_kind, Instant::now()));
                             state.dragging = Some(Dragging::Buffer);
                         } else if scrollbar_rect.contains(Point::new(x, y)) {
                             if let Some(start_scroll) = terminal.scrollbar() {
@@ -356,6 +580,29 @@ where
                         if let Some(on_middle_click) = &terminal_box.on_middle_click {
                             shell.publish(on_middle_click());
                         }
+                    } else if button == Button::Right
+                        && x >= 0.0
+                        && x < buffer_size.0.unwrap_or(0.0)
+                        && y >= 0.0
+                        && y < buffer_size.1.unwrap_or(0.0)
+                    {
+                        // Right click outside the selection extends it, inside it keeps the
+                        // selection for the context menu
+                        let location = terminal.viewport_to_point(TermPoint::new(
+                            row as usize,
+                            TermColumn(col as usize),
+                        ));
+                        let side = if col.fract() < 0.5 {
+                            TermSide::Left
+                        } else {
+                            TermSide::Right
+                        };
+                        if terminal
+                            .selection_range()
+                            .is_some_and(|range| !range.contains(location))
+                        {
+                            terminal.extend_selection(location, side);
+                        }
                     }
                     // Update context menu state
                     if let Some(on_context_menu) = &terminal_box.on_context_menu {
@@ -379,7 +626,7 @@ where
                 //TODO: better calculation of position
                 let col = x / terminal.size().cell_width;
                 let row = y / terminal.size().cell_height;
-                if is_mouse_mode {
+                if is_mouse_reporting {
                     terminal.report_mouse(event, &state.modifiers, col as u32, row as u32);
                 } else {
                     status = Status::Captured;
@@ -395,7 +642,7 @@ where
                 //TODO: better calculation of position
                 let col = x / terminal.size().cell_width;
                 let row = y / terminal.size().cell_height;
-                if is_mouse_mode {
+                if is_mouse_reporting {
                     terminal.report_mouse(event, &state.modifiers, col as u32, row as u32);
                 }
             }
@@ -414,13 +661,21 @@ where
                     terminal_box.mouse_inside_boundary = Some(mouse_is_inside);
                 }
             }
+            let point_opt = if state.dragging.is_none() {
+                cursor_position
+                    .position_in(layout.bounds())
+                    .and_then(|p| hint_point(&terminal, terminal_box.padding, buffer_size, p))
+            } else {
+                None
+            };
+            terminal.set_hovered_hint(point_opt, state.modifiers.control());
             if let Some(p) = cursor_position.position() {
                 let x = (p.x - layout.bounds().x) - terminal_box.padding.left;
                 let y = (p.y - layout.bounds().y) - terminal_box.padding.top;
                 //TODO: better calculation of position
                 let col = x / terminal.size().cell_width;
                 let row = y / terminal.size().cell_height;
-                if is_mouse_mode {
+                if is_mouse_reporting {
                     terminal.report_mouse(event, &state.modifiers, col as u32, row as u32);
                 } else {
                     if let Some(dragging) = &state.dragging {
@@ -468,19 +723,31 @@ where
                     let row = y / terminal.size().cell_height;
                     terminal.scroll_mouse(delta, &state.modifiers, col as u32, row as u32);
                 } else {
-                    match delta {
+                    // Programs on the alternate screen, like less, get arrow keys instead
+                    let alternate_scroll =
+                        term_mode.contains(TermMode::ALT_SCREEN | TermMode::ALTERNATE_SCROLL);
+                    let lines = match delta {
                         ScrollDelta::Lines { x: _, y } => {
-                            //TODO: this adjustment is just a guess!
                             state.scroll_pixels = 0.0;
-                            let lines = (-y * 6.0) as i32;
-                            if lines != 0 {
-                                terminal.scroll(TerminalScroll::Delta(-lines));
+                            if terminal_box.scroll_shift_page
+                                && state.modifiers.shift()
+                                && !alternate_scroll
+                                && y != 0.0
+                            {
+                                for _ in 0..(y.abs().round() as u32).max(1) {
+                                    terminal.scroll(if y > 0.0 {
+                                        TerminalScroll::PageUp
+                                    } else {
+                                        TerminalScroll::PageDown
+                                    });
+                                }
+                                0
+                            } else {
+                                (y * terminal_box.scroll_lines) as i32
                             }
-                            status = Status::Captured;
                         }
                         ScrollDelta::Pixels { x: _, y } => {
-                            //TODO: this adjustment is just a guess!
-                            state.scroll_pixels -= y * 6.0;
+                            state.scroll_pixels += y * terminal_box.scroll_pixels_multiplier;
                             let mut lines = 0;
                             let metrics = terminal.with_buffer(|buffer| buffer.metrics());
                             while state.scroll_pixels <= -metrics.line_height {
@@ -491,12 +758,23 @@ where
                                 lines += 1;
                                 state.scroll_pixels -= metrics.line_height;
                             }
-                            if lines != 0 {
-                                terminal.scroll(TerminalScroll::Delta(-lines));
-                            }
-                            status = Status::Captured;
+                            lines
+                        }
+                    };
+                    if lines != 0 {
+                        if alternate_scroll {
+                            let arrow: &[u8] = match (lines > 0, is_app_cursor) {
+                                (true, true) => b"\x1BOA",
+                                (true, false) => b"\x1B[A",
+                                (false, true) => b"\x1BOB",
+                                (false, false) => b"\x1B[B",
+                            };
+                            terminal.input_no_scroll(arrow.repeat(lines.unsigned_abs() as usize));
+                        } else {
+                            terminal.scroll(TerminalScroll::Delta(lines));
                         }
                     }
+                    status = Status::Captured;
                 }
             }
         }
@@ -506,6 +784,25 @@ where
     status
 }
 
+// Grid point under the pointer, if it is inside the text area
+fn hint_point(
+    terminal: &Terminal,
+    padding: Padding,
+    buffer_size: (Option<f32>, Option<f32>),
+    p: Point,
+) -> Option<TermPoint> {
+    let x = p.x - padding.left;
+    let y = p.y - padding.top;
+    if x < 0.0 || x >= buffer_size.0.unwrap_or(0.0) || y < 0.0 || y >= buffer_size.1.unwrap_or(0.0)
+    {
+        return None;
+    }
+    //TODO: better calculation of position
+    let col = x / terminal.size().cell_width;
+    let row = y / terminal.size().cell_height;
+    Some(terminal.viewport_to_point(TermPoint::new(row as usize, TermColumn(col as usize))))
+}
+
 /*
  shift     0b1         (1)
 alt       0b10        (2)
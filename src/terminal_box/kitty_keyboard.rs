@@ -0,0 +1,478 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+// Key encoding of the kitty keyboard protocol. Programs push and pop the enhancement flags with
+// `CSI > flags u` and `CSI < u`, which alacritty_terminal keeps as a stack per screen, and this
+// turns key events into escape codes for the active flags.
+// See https://sw.kovidgoyal.net/kitty/keyboard-protocol/
+
+use alacritty_terminal::term::TermMode;
+use cosmic::iced::keyboard::{key::Named, Key, Location, Modifiers};
+
+/// Kind of a key event, sent when the program asks for event types
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyEventKind {
+    Press = 1,
+    Repeat = 2,
+    Release = 3,
+}
+
+// Character of a key without Shift. Keys only come with the character they typed, so symbols
+// are mapped back to their key on a US layout
+fn unshifted(c: char) -> char {
+    match c {
+        '!' => '1',
+        '@' => '2',
+        '#' => '3',
+        '$' => '4',
+        '%' => '5',
+        '^' => '6',
+        '&' => '7',
+        '*' => '8',
+        '(' => '9',
+        ')' => '0',
+        '_' => '-',
+        '+' => '=',
+        '{' => '[',
+        '}' => ']',
+        '|' => '\\',
+        ':' => ';',
+        '"' => '\'',
+        '<' => ',',
+        '>' => '.',
+        '?' => '/',
+        '~' => '`',
+        _ => c.to_lowercase().next().unwrap_or(c),
+    }
+}
+
+// Escape code of a key without its modifiers, as the key number and the final byte
+fn key_code(key: &Key, location: Location, shift: bool) -> Option<(u32, char)> {
+    let code = match key {
+        Key::Character(c) => {
+            let mut chars = c.chars();
+            let c = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+            if location == Location::Numpad {
+                return match c {
+                    '0'..='9' => Some((57399 + u32::from(c) - u32::from('0'), 'u')),
+                    '.' => Some((57409, 'u')),
+                    '/' => Some((57410, 'u')),
+                    '*' => Some((57411, 'u')),
+                    '-' => Some((57412, 'u')),
+                    '+' => Some((57413, 'u')),
+                    '=' => Some((57415, 'u')),
+                    ',' => Some((57416, 'u')),
+                    _ => None,
+                };
+            }
+            // Keys are reported by their lowercase or unshifted character
+            let c = if shift {
+                unshifted(c)
+            } else {
+                c.to_lowercase().next().unwrap_or(c)
+            };
+            (c as u32, 'u')
+        }
+        Key::Named(named) => {
+            let right = location == Location::Right;
+            match named {
+                Named::Escape => (27, 'u'),
+                Named::Enter if location == Location::Numpad => (57414, 'u'),
+                Named::Enter => (13, 'u'),
+                Named::Tab => (9, 'u'),
+                Named::Backspace => (127, 'u'),
+                Named::Space => (32, 'u'),
+                Named::Insert => (2, '~'),
+                Named::Delete => (3, '~'),
+                Named::ArrowLeft => (1, 'D'),
+                Named::ArrowRight => (1, 'C'),
+                Named::ArrowUp => (1, 'A'),
+                Named::ArrowDown => (1, 'B'),
+                Named::PageUp => (5, '~'),
+                Named::PageDown => (6, '~'),
+                Named::Home => (1, 'H'),
+                Named::End => (1, 'F'),
+                Named::CapsLock => (57358, 'u'),
+                Named::ScrollLock => (57359, 'u'),
+                Named::NumLock => (57360, 'u'),
+                Named::PrintScreen => (57361, 'u'),
+                Named::Pause => (57362, 'u'),
+                Named::ContextMenu => (57363, 'u'),
+                Named::F1 => (1, 'P'),
+                Named::F2 => (1, 'Q'),
+                // CSI R is the cursor position report
+                Named::F3 => (13, '~'),
+                Named::F4 => (1, 'S'),
+                Named::F5 => (15, '~'),
+                Named::F6 => (17, '~'),
+                Named::F7 => (18, '~'),
+                Named::F8 => (19, '~'),
+                Named::F9 => (20, '~'),
+                Named::F10 => (21, '~'),
+                Named::F11 => (23, '~'),
+                Named::F12 => (24, '~'),
+                Named::F13 => (57376, 'u'),
+                Named::F14 => (57377, 'u'),
+                Named::F15 => (57378, 'u'),
+                Named::F16 => (57379, 'u'),
+                Named::F17 => (57380, 'u'),
+                Named::F18 => (57381, 'u'),
+                Named::F19 => (57382, 'u'),
+                Named::F20 => (57383, 'u'),
+                Named::F21 => (57384, 'u'),
+                Named::F22 => (57385, 'u'),
+                Named::F23 => (57386, 'u'),
+                Named::F24 => (57387, 'u'),
+                Named::F25 => (57388, 'u'),
+                Named::MediaPlay => (57428, 'u'),
+                Named::MediaPause => (57429, 'u'),
+                Named::MediaPlayPause => (57430, 'u'),
+                Named::MediaStop => (57432, 'u'),
+                Named::MediaFastForward => (57433, 'u'),
+                Named::MediaRewind => (57434, 'u'),
+                Named::MediaTrackNext => (57435, 'u'),
+                Named::MediaTrackPrevious => (57436, 'u'),
+                Named::MediaRecord => (57437, 'u'),
+                Named::AudioVolumeDown => (57438, 'u'),
+                Named::AudioVolumeUp => (57439, 'u'),
+                Named::AudioVolumeMute => (57440, 'u'),
+                Named::Shift => (if right { 57447 } else { 57441 }, 'u'),
+                Named::Control => (if right { 57448 } else { 57442 }, 'u'),
+                Named::Alt => (if right { 57449 } else { 57443 }, 'u'),
+                Named::Super => (if right { 57450 } else { 57444 }, 'u'),
+                Named::Hyper => (if right { 57451 } else { 57445 }, 'u'),
+                Named::Meta => (if right { 57452 } else { 57446 }, 'u'),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Key that a release is matched with its press by. The character of a key changes when Shift
+/// is pressed or released while the key is held.
+pub fn pressed_key(key: &Key) -> Key {
+    match key {
+        Key::Character(c) => Key::Character(c.chars().map(unshifted).collect::<String>().into()),
+        _ => key.clone(),
+    }
+}
+
+/// Modifier bits of the protocol, before adding one
+pub fn modifier_bits(modifiers: Modifiers) -> u8 {
+    let mut bits = 0;
+    if modifiers.shift() {
+        bits |= 1;
+    }
+    if modifiers.alt() {
+        bits |= 2;
+    }
+    if modifiers.control() {
+        bits |= 4;
+    }
+    if modifiers.logo() {
+        bits |= 8;
+    }
+    bits
+}
+
+/// Encode a key event for the flags in `mode`. Returns `None` when the legacy encoding should
+/// be used instead, and an empty code when nothing is sent for the event.
+pub fn encode(
+    mode: TermMode,
+    key: &Key,
+    location: Location,
+    modifiers: Modifiers,
+    text: Option<&str>,
+    kind: KeyEventKind,
+) -> Option<Vec<u8>> {
+    let all_as_escape = mode.contains(TermMode::REPORT_ALL_KEYS_AS_ESC);
+    let event_types = mode.contains(TermMode::REPORT_EVENT_TYPES);
+    let legacy_text_key = matches!(
+        key,
+        Key::Named(Named::Enter | Named::Tab | Named::Backspace)
+    );
+
+    // Enter, Tab and Backspace keep working in a shell left in this mode by a crashed program
+    if kind == KeyEventKind::Release && (!event_types || (legacy_text_key && !all_as_escape)) {
+        return Some(Vec::new());
+    }
+
+    let bits = modifier_bits(modifiers);
+    let text = text.filter(|text| !text.is_empty() && !text.chars().any(char::is_control));
+    let disambiguate = mode.contains(TermMode::DISAMBIGUATE_ESC_CODES)
+        && (*key == Key::Named(Named::Escape) || bits & !1 != 0 || location == Location::Numpad);
+    // Repeated text is sent again as text
+    let repeat = event_types && kind == KeyEventKind::Repeat && text.is_none() && !legacy_text_key;
+    let is_modifier = matches!(
+        key,
+        Key::Named(
+            Named::Shift | Named::Control | Named::Alt | Named::Super | Named::Hyper | Named::Meta
+        )
+    );
+    if !all_as_escape && (is_modifier || !(disambiguate || repeat || kind == KeyEventKind::Release))
+    {
+        return None;
+    }
+
+    let Some((number, terminator)) = key_code(key, location, modifiers.shift()) else {
+        // Text without a key code, like from a compose sequence, is sent as is
+        return text.map(|text| text.as_bytes().to_vec());
+    };
+
+    let mut code = String::from("\x1B[");
+    let event_type = if event_types && kind != KeyEventKind::Press {
+        Some(kind as u8)
+    } else {
+        None
+    };
+    let associated_text = text.filter(|_| {
+        all_as_escape
+            && mode.contains(TermMode::REPORT_ASSOCIATED_TEXT)
+            && kind != KeyEventKind::Release
+    });
+    let extra_fields = bits != 0 || event_type.is_some() || associated_text.is_some();
+    // Legacy style keys leave out the key number when it is one and nothing follows
+    if terminator == 'u' || number != 1 || extra_fields {
+        code.push_str(&number.to_string());
+    }
+    // The shifted character is only reported as the alternate key, from the key or its text
+    if terminator == 'u' && mode.contains(TermMode::REPORT_ALTERNATE_KEYS) && modifiers.shift() {
+        let single = |text: &str| {
+            let mut chars = text.chars();
+            chars.next().filter(|_| chars.next().is_none())
+        };
+        let key_char = match key {
+            Key::Character(c) => single(c),
+            _ => None,
+        };
+        let shifted = [key_char, text.and_then(single)]
+            .into_iter()
+            .flatten()
+            .find(|shifted| *shifted as u32 != number);
+        if let Some(shifted) = shifted {
+            code.push_str(&format!(":{}", shifted as u32));
+        }
+    }
+    if extra_fields {
+        code.push_str(&format!(";{}", bits + 1));
+        if let Some(event_type) = event_type {
+            code.push_str(&format!(":{event_type}"));
+        }
+    }
+    if let Some(text) = associated_text {
+        let codepoints: Vec<String> = text.chars().map(|c| (c as u32).to_string()).collect();
+        code.push_str(&format!(";{}", codepoints.join(":")));
+    }
+    code.push(terminator);
+    Some(code.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character(c: &str) -> Key {
+        Key::Character(c.into())
+    }
+
+    fn press(
+        mode: TermMode,
+        key: &Key,
+        modifiers: Modifiers,
+        text: Option<&str>,
+    ) -> Option<String> {
+        encode(
+            mode,
+            key,
+            Location::Standard,
+            modifiers,
+            text,
+            KeyEventKind::Press,
+        )
+        .map(|code| String::from_utf8(code).unwrap())
+    }
+
+    #[test]
+    fn disambiguate() {
+        let mode = TermMode::DISAMBIGUATE_ESC_CODES;
+        let a = character("a");
+        assert_eq!(press(mode, &a, Modifiers::empty(), Some("a")), None);
+        assert_eq!(
+            press(mode, &a, Modifiers::CTRL, None).as_deref(),
+            Some("\x1B[97;5u")
+        );
+        assert_eq!(
+            press(
+                mode,
+                &character("A"),
+                Modifiers::CTRL | Modifiers::SHIFT,
+                None
+            )
+            .as_deref(),
+            Some("\x1B[97;6u")
+        );
+        assert_eq!(
+            press(mode, &Key::Named(Named::Escape), Modifiers::empty(), None).as_deref(),
+            Some("\x1B[27u")
+        );
+        assert_eq!(
+            press(mode, &Key::Named(Named::ArrowUp), Modifiers::CTRL, None).as_deref(),
+            Some("\x1B[1;5A")
+        );
+        assert_eq!(
+            press(mode, &Key::Named(Named::ArrowUp), Modifiers::empty(), None),
+            None
+        );
+        assert_eq!(
+            press(mode, &Key::Named(Named::Shift), Modifiers::SHIFT, None),
+            None
+        );
+    }
+
+    #[test]
+    fn numpad() {
+        let code = encode(
+            TermMode::DISAMBIGUATE_ESC_CODES,
+            &character("1"),
+            Location::Numpad,
+            Modifiers::empty(),
+            Some("1"),
+            KeyEventKind::Press,
+        );
+        assert_eq!(code.as_deref(), Some(&b"\x1B[57400u"[..]));
+    }
+
+    #[test]
+    fn all_keys_as_escape() {
+        let mode = TermMode::DISAMBIGUATE_ESC_CODES | TermMode::REPORT_ALL_KEYS_AS_ESC;
+        assert_eq!(
+            press(mode, &character("a"), Modifiers::empty(), Some("a")).as_deref(),
+            Some("\x1B[97u")
+        );
+        assert_eq!(
+            press(mode, &Key::Named(Named::ArrowUp), Modifiers::empty(), None).as_deref(),
+            Some("\x1B[A")
+        );
+        assert_eq!(
+            press(mode, &Key::Named(Named::Shift), Modifiers::SHIFT, None).as_deref(),
+            Some("\x1B[57441;2u")
+        );
+        assert_eq!(
+            press(
+                mode | TermMode::REPORT_ALTERNATE_KEYS,
+                &character("a"),
+                Modifiers::SHIFT,
+                Some("A")
+            )
+            .as_deref(),
+            Some("\x1B[97:65;2u")
+        );
+        assert_eq!(
+            press(
+                mode | TermMode::REPORT_ASSOCIATED_TEXT,
+                &character("a"),
+                Modifiers::empty(),
+                Some("a")
+            )
+            .as_deref(),
+            Some("\x1B[97;1;97u")
+        );
+    }
+
+    #[test]
+    fn shifted_symbol() {
+        let mode = TermMode::DISAMBIGUATE_ESC_CODES | TermMode::REPORT_ALL_KEYS_AS_ESC;
+        let bang = character("!");
+        // The key number is the unshifted key, the shifted character is only an alternate
+        assert_eq!(
+            press(mode, &bang, Modifiers::SHIFT, Some("!")).as_deref(),
+            Some("\x1B[49;2u")
+        );
+        assert_eq!(
+            press(
+                mode | TermMode::REPORT_ALTERNATE_KEYS,
+                &bang,
+                Modifiers::SHIFT,
+                Some("!")
+            )
+            .as_deref(),
+            Some("\x1B[49:33;2u")
+        );
+        assert_eq!(
+            press(
+                TermMode::DISAMBIGUATE_ESC_CODES | TermMode::REPORT_ALTERNATE_KEYS,
+                &character("{"),
+                Modifiers::CTRL | Modifiers::SHIFT,
+                None
+            )
+            .as_deref(),
+            Some("\x1B[91:123;6u")
+        );
+        // Without Shift, a symbol is its own key
+        assert_eq!(
+            press(mode, &bang, Modifiers::empty(), Some("!")).as_deref(),
+            Some("\x1B[33u")
+        );
+    }
+
+    #[test]
+    fn event_types() {
+        let mode = TermMode::DISAMBIGUATE_ESC_CODES | TermMode::REPORT_EVENT_TYPES;
+        let a = character("a");
+        let event = |key: &Key, modifiers: Modifiers, text: Option<&str>, kind: KeyEventKind| {
+            encode(mode, key, Location::Standard, modifiers, text, kind)
+                .map(|code| String::from_utf8(code).unwrap())
+        };
+        assert_eq!(
+            event(&a, Modifiers::empty(), None, KeyEventKind::Release).as_deref(),
+            Some("\x1B[97;1:3u")
+        );
+        assert_eq!(
+            event(&a, Modifiers::CTRL, None, KeyEventKind::Repeat).as_deref(),
+            Some("\x1B[97;5:2u")
+        );
+        // Repeated text is sent as text
+        assert_eq!(
+            event(&a, Modifiers::empty(), Some("a"), KeyEventKind::Repeat),
+            None
+        );
+        // Enter stays usable in a shell left in this mode
+        assert_eq!(
+            event(
+                &Key::Named(Named::Enter),
+                Modifiers::empty(),
+                None,
+                KeyEventKind::Release
+            )
+            .as_deref(),
+            Some("")
+        );
+    }
+
+    #[test]
+    fn release_without_event_types() {
+        let code = encode(
+            TermMode::DISAMBIGUATE_ESC_CODES,
+            &character("a"),
+            Location::Standard,
+            Modifiers::CTRL,
+            None,
+            KeyEventKind::Release,
+        );
+        assert_eq!(code, Some(Vec::new()));
+    }
+
+    #[test]
+    fn pressed_key_ignores_case() {
+        assert_eq!(pressed_key(&character("A")), character("a"));
+        assert_eq!(pressed_key(&character("!")), character("1"));
+        assert_eq!(
+            pressed_key(&Key::Named(Named::Enter)),
+            Key::Named(Named::Enter)
+        );
+    }
+}
@@ -1,5 +1,7 @@
 mod drawer;
 mod enums;
 mod event_handler;
+mod kitty_keyboard;
+mod modify_other_keys;
 mod state;
 pub mod terminal_box;
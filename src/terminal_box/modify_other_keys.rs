@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+// Key encoding of xterm's modifyOtherKeys, which sends modified keys as `CSI 27 ; modifiers ;
+// key ~` when the legacy encoding would lose the modifiers. Applications set the level with
+// `CSI > 4 ; level m`, see the tap module.
+// See https://invisible-island.net/xterm/modified-keys.html
+
+use cosmic::iced::keyboard::{key::Named, Key, Modifiers};
+
+use super::kitty_keyboard::modifier_bits;
+
+// Characters that Ctrl turns into C0 control codes in the legacy encoding
+fn has_control_code(c: char) -> bool {
+    c.is_ascii_alphabetic() || matches!(c, '@' | '[' | '\\' | ']' | '^' | '_' | ' ' | '?')
+}
+
+/// Encode a key press for modifyOtherKeys `level` 1 or 2, returns `None` when the legacy
+/// encoding should be used
+pub fn encode(level: u8, key: &Key, modifiers: Modifiers, text: Option<&str>) -> Option<Vec<u8>> {
+    // Super is handled by the desktop
+    if level == 0 || modifiers.logo() {
+        return None;
+    }
+
+    let shift = modifiers.shift();
+    let control = modifiers.control();
+    let alt = modifiers.alt();
+    let code = match key {
+        Key::Character(c) => {
+            let mut chars = c.chars();
+            let base = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+            let encode = match level {
+                // Only keys that the legacy encoding cannot tell apart
+                1 => control && (shift || !has_control_code(base)),
+                _ => control || alt,
+            };
+            if !encode {
+                return None;
+            }
+            // The key is reported as the character it types with Shift
+            let shifted = text.and_then(|text| {
+                let mut chars = text.chars();
+                chars
+                    .next()
+                    .filter(|c| !c.is_control() && chars.next().is_none())
+            });
+            match shifted {
+                Some(shifted) => shifted as u32,
+                None if shift => base.to_ascii_uppercase() as u32,
+                None => base as u32,
+            }
+        }
+        Key::Named(named) => {
+            let (code, encode) = match named {
+                Named::Enter => (13, control || shift),
+                Named::Escape => (27, control || shift),
+                Named::Tab => (9, control),
+                Named::Space => (32, shift),
+                Named::Backspace => (127, shift),
+                _ => return None,
+            };
+            if !(encode || (level >= 2 && (control || alt || shift))) {
+                return None;
+            }
+            code
+        }
+        _ => return None,
+    };
+
+    Some(format!("\x1B[27;{};{}~", modifier_bits(modifiers) + 1, code).into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(level: u8, key: &Key, modifiers: Modifiers, text: Option<&str>) -> Option<String> {
+        encode(level, key, modifiers, text).map(|code| String::from_utf8(code).unwrap())
+    }
+
+    #[test]
+    fn level_one() {
+        let a = Key::Character("a".into());
+        assert_eq!(code(1, &a, Modifiers::empty(), Some("a")), None);
+        // Ctrl+a is a control code in the legacy encoding
+        assert_eq!(code(1, &a, Modifiers::CTRL, None), None);
+        assert_eq!(
+            code(1, &a, Modifiers::CTRL | Modifiers::SHIFT, None).as_deref(),
+            Some("\x1B[27;6;65~")
+        );
+        assert_eq!(
+            code(1, &Key::Character("1".into()), Modifiers::CTRL, None).as_deref(),
+            Some("\x1B[27;5;49~")
+        );
+        assert_eq!(code(1, &a, Modifiers::ALT, Some("a")), None);
+        assert_eq!(
+            code(1, &Key::Named(Named::Enter), Modifiers::CTRL, None).as_deref(),
+            Some("\x1B[27;5;13~")
+        );
+        assert_eq!(
+            code(1, &Key::Named(Named::Tab), Modifiers::SHIFT, None),
+            None
+        );
+    }
+
+    #[test]
+    fn level_two() {
+        let a = Key::Character("a".into());
+        assert_eq!(code(2, &a, Modifiers::empty(), Some("a")), None);
+        assert_eq!(
+            code(2, &a, Modifiers::CTRL, None).as_deref(),
+            Some("\x1B[27;5;97~")
+        );
+        assert_eq!(
+            code(2, &a, Modifiers::ALT, Some("a")).as_deref(),
+            Some("\x1B[27;3;97~")
+        );
+        assert_eq!(
+            code(2, &Key::Named(Named::Tab), Modifiers::SHIFT, None).as_deref(),
+            Some("\x1B[27;2;9~")
+        );
+    }
+
+    #[test]
+    fn ignored() {
+        let a = Key::Character("a".into());
+        assert_eq!(code(0, &a, Modifiers::CTRL, None), None);
+        // Super is handled by the desktop
+        assert_eq!(code(2, &a, Modifiers::CTRL | Modifiers::LOGO, None), None);
+        assert_eq!(
+            code(2, &Key::Named(Named::ArrowUp), Modifiers::CTRL, None),
+            None
+        );
+    }
+}
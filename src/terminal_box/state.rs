@@ -1,9 +1,21 @@
+use alacritty_terminal::term::TermMode;
+
 use cosmic::{
-    iced::{keyboard::Modifiers, Rectangle},
-    iced_core::widget::operation::{self},
+    iced::{
+        keyboard::{Key, Modifiers},
+        Rectangle,
+    },
+    iced_core::{
+        image,
+        widget::operation::{self},
+    },
 };
 
-use std::{cell::Cell, time::Instant};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
 
 use super::enums::{ClickKind, Dragging};
 
@@ -11,7 +23,13 @@ pub struct State {
     pub(super) modifiers: Modifiers,
     pub(super) click: Option<(ClickKind, Instant)>,
     pub(super) dragging: Option<Dragging>,
+    // Rendered builtin glyphs by character, width, height and color
+    pub(super) glyph_handles: RefCell<HashMap<(char, usize, usize, u32), image::Handle>>,
     pub(super) is_focused: bool,
+    // Kitty keyboard protocol flags of the terminal, and the keys sent with them and not
+    // released yet
+    pub(super) kitty_keyboard_mode: TermMode,
+    pub(super) pressed_keys: HashSet<Key>,
     pub(super) scroll_pixels: f32,
     pub(super) scrollbar_rect: Cell<Rectangle<f32>>,
 }
@@ -23,7 +41,10 @@ impl State {
             modifiers: Modifiers::empty(),
             click: None,
             dragging: None,
+            glyph_handles: RefCell::new(HashMap::new()),
             is_focused: false,
+            kitty_keyboard_mode: TermMode::empty(),
+            pressed_keys: HashSet::new(),
             scroll_pixels: 0.0,
             scrollbar_rect: Cell::new(Rectangle::default()),
         }
@@ -41,5 +62,6 @@ impl operation::Focusable for State {
 
     fn unfocus(&mut self) {
         self.is_focused = false;
+        self.pressed_keys.clear();
     }
 }
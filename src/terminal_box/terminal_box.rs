@@ -22,14 +22,14 @@ use cosmic::{
     Renderer,
 };
 
-use std::{collections::HashMap, sync::Mutex, time::Duration};
+use std::{borrow::Cow, collections::HashMap, sync::Mutex, time::Duration};
 
 use super::enums::Dragging;
 use super::state::State;
 use super::{drawer, event_handler};
 
 use crate::app::action::Action;
-use crate::{key_bind::key_binds, Terminal};
+use crate::Terminal;
 
 pub struct TerminalBox<'a, Message> {
     pub(super) terminal: &'a Mutex<Terminal>,
@@ -43,7 +43,12 @@ pub struct TerminalBox<'a, Message> {
     pub(super) opacity: Option<f32>,
     pub(super) mouse_inside_boundary: Option<bool>,
     pub(super) on_middle_click: Option<Box<dyn Fn() -> Message + 'a>>,
-    pub(super) key_binds: HashMap<KeyBind, Action>,
+    pub(super) on_open_url: Option<Box<dyn Fn(String) -> Message + 'a>>,
+    pub(super) on_vi_search: Option<Box<dyn Fn(bool) -> Message + 'a>>,
+    pub(super) key_binds: Cow<'a, HashMap<KeyBind, Action>>,
+    pub(super) scroll_lines: f32,
+    pub(super) scroll_pixels_multiplier: f32,
+    pub(super) scroll_shift_page: bool,
 }
 
 impl<'a, Message> TerminalBox<'a, Message>
@@ -63,7 +68,12 @@ where
             opacity: None,
             mouse_inside_boundary: None,
             on_middle_click: None,
-            key_binds: key_binds(),
+            on_open_url: None,
+            on_vi_search: None,
+            key_binds: Cow::Owned(HashMap::new()),
+            scroll_lines: 6.0,
+            scroll_pixels_multiplier: 1.0,
+            scroll_shift_page: true,
         }
     }
 
@@ -110,10 +120,44 @@ where
         self
     }
 
+    pub fn on_open_url(mut self, on_open_url: impl Fn(String) -> Message + 'a) -> Self {
+        self.on_open_url = Some(Box::new(on_open_url));
+        self
+    }
+
+    /// Called with the search direction when `/` or `?` is pressed in vi mode
+    pub fn on_vi_search(mut self, on_vi_search: impl Fn(bool) -> Message + 'a) -> Self {
+        self.on_vi_search = Some(Box::new(on_vi_search));
+        self
+    }
+
     pub fn opacity(mut self, opacity: f32) -> Self {
         self.opacity = Some(opacity);
         self
     }
+
+    pub fn key_binds(mut self, key_binds: &'a HashMap<KeyBind, Action>) -> Self {
+        self.key_binds = Cow::Borrowed(key_binds);
+        self
+    }
+
+    /// Lines scrolled for each notch of a mouse wheel
+    pub fn scroll_lines(mut self, scroll_lines: f32) -> Self {
+        self.scroll_lines = scroll_lines;
+        self
+    }
+
+    /// Multiplier of pixel scrolling from touchpads
+    pub fn scroll_pixels_multiplier(mut self, scroll_pixels_multiplier: f32) -> Self {
+        self.scroll_pixels_multiplier = scroll_pixels_multiplier;
+        self
+    }
+
+    /// Scroll a page for each notch of a mouse wheel while Shift is held
+    pub fn scroll_shift_page(mut self, scroll_shift_page: bool) -> Self {
+        self.scroll_shift_page = scroll_shift_page;
+        self
+    }
 }
 
 pub fn terminal_box<Message>(terminal: &Mutex<Terminal>) -> TerminalBox<'_, Message>
@@ -201,6 +245,9 @@ where
 
         if let Some(p) = cursor_position.position_in(layout.bounds()) {
             let terminal = self.terminal.lock().unwrap();
+            if terminal.hovered_hint().is_some() {
+                return mouse::Interaction::Pointer;
+            }
             let buffer_size = terminal.with_buffer(|buffer| buffer.size());
 
             let x = p.x - self.padding.left;
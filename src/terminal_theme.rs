@@ -59,6 +59,44 @@ impl ColorDerive {
         Self::okhsl_to_rgb(okhsl)
     }
 
+    // Change the lightness of `fg` as little as possible to reach `min_contrast` with `bg`
+    fn contrast_adj(fg: Rgb, bg: Rgb, min_contrast: f64) -> Rgb {
+        if fg.contrast(bg) >= min_contrast {
+            return fg;
+        }
+
+        let okhsl = Self::rgb_to_okhsl(fg);
+        let with_lightness = |lightness: f32| {
+            let mut okhsl = okhsl;
+            okhsl.lightness = lightness;
+            Self::okhsl_to_rgb(okhsl)
+        };
+
+        // Move towards white or black, whichever contrasts more with the background
+        let target = if with_lightness(1.0).contrast(bg) >= with_lightness(0.0).contrast(bg) {
+            1.0
+        } else {
+            0.0
+        };
+        let extreme = with_lightness(target);
+        if extreme.contrast(bg) < min_contrast {
+            return extreme;
+        }
+
+        // Lightness between `failing` and `passing` is searched for the smallest change
+        let mut failing = okhsl.lightness;
+        let mut passing = target;
+        for _ in 0..8 {
+            let lightness = (failing + passing) / 2.0;
+            if with_lightness(lightness).contrast(bg) >= min_contrast {
+                passing = lightness;
+            } else {
+                failing = lightness;
+            }
+        }
+        with_lightness(passing)
+    }
+
     fn brighten(&self, rgb: Rgb) -> Rgb {
         let saturation_adj = self.bright_saturation_adjustment;
         let lightness_adj = self.bright_lightness_adjustment;
@@ -110,6 +148,12 @@ impl ColorDerive {
     }
 }
 
+/// Adjust the lightness of a foreground color until its contrast ratio with the background is
+/// at least `min_contrast`, keeping its hue and saturation
+pub fn min_contrast(fg: Rgb, bg: Rgb, min_contrast: f64) -> Rgb {
+    ColorDerive::contrast_adj(fg, bg, min_contrast)
+}
+
 fn auto_colors() -> Colors {
     let mut colors = Colors::default();
 
@@ -210,6 +254,8 @@ impl From<(&str, &Colors)> for ColorScheme {
             foreground: encode_rgb(colors[NamedColor::Foreground]),
             background: encode_rgb(colors[NamedColor::Background]),
             cursor: encode_rgb(colors[NamedColor::Cursor]),
+            // Not part of the terminal colors, only set by custom color schemes
+            cursor_text: None,
             bright_foreground: encode_rgb(colors[NamedColor::BrightForeground]),
             dim_foreground: encode_rgb(colors[NamedColor::DimForeground]),
             normal: ColorSchemeAnsi {
@@ -381,3 +427,68 @@ pub fn export() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgb = Rgb { r: 0, g: 0, b: 0 };
+    const WHITE: Rgb = Rgb {
+        r: 0xFF,
+        g: 0xFF,
+        b: 0xFF,
+    };
+
+    #[test]
+    fn failing_pair_lifted() {
+        let bg = Rgb {
+            r: 0x60,
+            g: 0x60,
+            b: 0x60,
+        };
+        for fg in [
+            Rgb {
+                r: 0x80,
+                g: 0x80,
+                b: 0x80,
+            },
+            Rgb {
+                r: 0x40,
+                g: 0x60,
+                b: 0xA0,
+            },
+        ] {
+            assert!(fg.contrast(bg) < 4.5);
+            let adjusted = min_contrast(fg, bg, 4.5);
+            assert!(adjusted.contrast(bg) >= 4.5, "{fg:?} became {adjusted:?}");
+            // White passes too, so the search should stop short of it
+            assert_ne!(adjusted, WHITE);
+        }
+    }
+
+    #[test]
+    fn passing_pair_unchanged() {
+        let fg = Rgb {
+            r: 0xC0,
+            g: 0x40,
+            b: 0x40,
+        };
+        assert_eq!(min_contrast(fg, BLACK, 3.0), fg);
+        assert_eq!(min_contrast(WHITE, BLACK, 7.0), WHITE);
+        assert_eq!(min_contrast(fg, fg, 1.0), fg);
+    }
+
+    #[test]
+    fn extreme_still_fails() {
+        // Neither white nor black reaches the ratio, so the better of the two is used
+        let gray = Rgb {
+            r: 0x80,
+            g: 0x80,
+            b: 0x80,
+        };
+        let adjusted = min_contrast(gray, gray, 7.0);
+        assert_eq!(adjusted, BLACK);
+        assert!(adjusted.contrast(gray) < 7.0);
+        assert!(adjusted.contrast(gray) > WHITE.contrast(gray));
+    }
+}